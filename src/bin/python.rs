@@ -1,28 +1,95 @@
 #![forbid(unsafe_code)]
 
-use std::{env, fmt, io};
-use std::ffi::OsStr;
+use std::{env, fmt, fs, io};
+use std::ffi::{OsStr, OsString};
 use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
+use std::process;
 use std::process::Command;
 
 use anyhow::Context;
 use apply::Apply;
 use is_executable::IsExecutable;
 use print_bytes::println_bytes;
+use serde::Serialize;
 use thiserror::Error;
 
-use pyenv_python::{HasPython, Python};
+use pyenv_python::{
+    config, trace, EnvFile, EnvFileError, HasPython, InstalledVersion, InstalledVersionKind, PipxApp, PipxHome,
+    PromptTemplate, PyenvError, PyenvRoot, PyenvRootError, PyenvVersion, Python, PythonError, PythonImplementation,
+    PythonInfo, PythonPrefix, Virtualenv,
+};
 
-use crate::Argv0ProgramType::{Binary, PythonScript, Script};
+use crate::Argv0ProgramType::{Binary, MainDir, PythonScript, Script, Zipapp};
+
+/// An executable binary format, recognized by its magic number.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+enum BinaryFormat {
+    Elf,
+    MachO,
+    /// A Windows `.exe` (which starts with a DOS `MZ` header).
+    /// Only executable on Windows.
+    Pe,
+}
+
+impl BinaryFormat {
+    fn detect(magic: &[u8; 4]) -> Option<Self> {
+        use BinaryFormat::*;
+        match magic {
+            b"\x7fELF" => Some(Elf),
+            [0xfe, 0xed, 0xfa, 0xce | 0xcf]
+            | [0xce | 0xcf, 0xfa, 0xed, 0xfe]
+            | [0xca, 0xfe, 0xba, 0xbe] => Some(MachO),
+            [b'M', b'Z', _, _] => Some(Pe),
+            _ => None,
+        }
+    }
+}
+
+impl Display for BinaryFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Elf => "ELF",
+            Self::MachO => "Mach-O",
+            Self::Pe => "PE",
+        };
+        write!(f, "{}", name)
+    }
+}
 
 #[derive(Eq, PartialEq, Debug)]
 enum Argv0ProgramType {
-    Binary,
+    Binary(BinaryFormat),
     PythonScript,
     Script,
+    /// A zip file runnable by `python`, usually a `.pyz` with a shebang line prepended.
+    /// `python_name` is the `python` named in the shebang, like `python3.9`, if any.
+    Zipapp {
+        python_name: Option<String>,
+    },
+    /// A directory with a `__main__.py`, which `python` can run like a zipapp.
+    MainDir,
+}
+
+/// Where an [`Argv0Program`] was found.
+#[derive(Debug)]
+enum Argv0ProgramSource {
+    /// In the same directory as the current `python`.
+    Python,
+    /// In a `pipx` venv, if it's not in the current `python`'s directory.
+    /// Only looked up if `$PYENV_PYTHON_PIPX` is set.
+    Pipx(PipxApp),
+}
+
+/// The chain of symlinks from argv0 to the current executable,
+/// with argv0 resolved through `$PATH` if it's a bare name.
+#[derive(Debug, Default)]
+struct Argv0Chain {
+    links: Vec<PathBuf>,
+    /// The file name of the current executable.
+    exe_name: Option<OsString>,
 }
 
 #[derive(Debug)]
@@ -30,6 +97,8 @@ struct Argv0Program {
     python_path: PathBuf,
     path: PathBuf,
     exe_type: Argv0ProgramType,
+    source: Argv0ProgramSource,
+    chain: Argv0Chain,
 }
 
 impl Argv0Program {
@@ -40,6 +109,18 @@ impl Argv0Program {
     pub fn path(&self) -> &Path {
         self.path.as_path()
     }
+    
+    pub fn exe_type(&self) -> &Argv0ProgramType {
+        &self.exe_type
+    }
+    
+    pub fn source(&self) -> &Argv0ProgramSource {
+        &self.source
+    }
+    
+    pub fn chain(&self) -> &Argv0Chain {
+        &self.chain
+    }
 }
 
 #[derive(Debug, Error)]
@@ -49,12 +130,18 @@ struct Argv0ProgramError {
     message: &'static str,
     #[source]
     source: Option<io::Error>,
+    /// If the program doesn't exist, i.e. the command wasn't found.
+    is_not_found: bool,
 }
 
 impl Argv0ProgramError {
     fn err(self) -> Result<(), Argv0ProgramError> {
         Err(self)
     }
+    
+    fn is_not_found(&self) -> bool {
+        self.is_not_found
+    }
 }
 
 struct PathBufError<'a> {
@@ -77,6 +164,16 @@ impl<'a> PathBufError<'a> {
             path: self.path(),
             message,
             source: None,
+            is_not_found: false,
+        }
+    }
+    
+    fn not_found(&self) -> Argv0ProgramError {
+        Argv0ProgramError {
+            path: self.path(),
+            message: "does not exist",
+            source: None,
+            is_not_found: true,
         }
     }
     
@@ -85,6 +182,7 @@ impl<'a> PathBufError<'a> {
             path: self.path(),
             message: "",
             source: Some(source),
+            is_not_found: false,
         }
     }
 }
@@ -92,28 +190,42 @@ impl<'a> PathBufError<'a> {
 impl Argv0ProgramType {
     /// Detect the type of argv0 in `python`'s directory.
     /// `path` is already in `python`'s directory.
-    /// First, check if it's an existing executable file.
-    /// Then, check if it's a script by looking for a shebang #!.
+    /// First, check if it's an existing file (or a `__main__` directory).
+    /// Then, check if it's a script by looking for a shebang #!,
+    /// and if it's a zipapp by looking for the zip magic number after any shebang line.
     ///
-    /// If it's not a script, then we assume it's a binary executable and we execute it as argv0.
+    /// If it's not a script, then check if it's a binary executable by its magic number
+    /// (see [`BinaryFormat`]), and if so, we execute it as argv0.
     /// This includes the normal argv0 == "python" case.
+    /// If it's neither, it's an error, since the OS won't be able to execute it either
+    /// (unless it has a `.py` extension, in which case it's run as a Python script).
     ///
     /// If it's a script, look for "python" in the shebang line.
     /// If it's a Python script, execute argv0 as "python" normally,
     /// with the script path inserted as argv1 so python can run it.
     /// It it's not a Python script, then just execute it as argv0,
     /// letting the OS run its shebang program.
+    ///
+    /// Zipapps and directories with a `__main__.py` are run by `python` the same way as Python scripts.
+    ///
+    /// Python scripts don't have to be executable, since they're passed to `python` anyways,
+    /// which helps when pip-installed scripts lose their mode bits.
+    /// Non-executable files are run as Python scripts if they have a Python shebang
+    /// or a `.py` or `.pyz` extension.
     fn detect(path: &Path) -> Result<Self, Argv0ProgramError> {
         let error = PathBufError::new(path);
         let with_src = |msg| error.using_message(msg).err();
         let with_err = |e| error.using_source(e);
         
         if !path.exists() {
-            with_src("does not exist")?;
+            error.not_found().err()?;
+        } else if path.is_dir() {
+            if !path.join("__main__.py").is_file() {
+                with_src("a directory without a __main__.py")?;
+            }
+            return Ok(MainDir);
         } else if !path.is_file() {
             with_src("not a file")?;
-        } else if !path.is_executable() {
-            with_src("not executable")?;
         }
         // checked the file already, so shouldn't have errors reading it,
         // so I'm not adding any context to the default anyhow::Error
@@ -123,42 +235,251 @@ impl Argv0ProgramType {
         reader.read(&mut shebang).map_err(with_err)?;
         let is_script = &shebang == b"#!";
         let exe_type = if !is_script {
-            Binary
+            let mut zip_magic = [0_u8; 4];
+            zip_magic[..2].copy_from_slice(&shebang);
+            reader.read(&mut zip_magic[2..]).map_err(with_err)?;
+            if is_zip_magic(&zip_magic) {
+                Some(Zipapp {
+                    python_name: None,
+                })
+            } else {
+                BinaryFormat::detect(&zip_magic).map(Binary)
+            }
         } else {
             // need to read shebang first before first line
             // b/c if there's no shebang and it's binary,
             // it might be UTF-8, so String decoding will fail
             let mut first_line = String::new();
             reader.read_line(&mut first_line).map_err(with_err)?;
+            let mut zip_magic = [0_u8; 4];
+            reader.read(&mut zip_magic).map_err(with_err)?;
             let is_python_script = ["python", "pip"]
                 .iter()
                 .any(|word| first_line.contains(word));
-            if is_python_script {
+            Some(if is_zip_magic(&zip_magic) {
+                Zipapp {
+                    python_name: shebang_python_name(first_line.as_str()),
+                }
+            } else if is_python_script {
                 PythonScript
             } else {
                 Script
-            }
+            })
         };
         
-        Ok(exe_type)
+        let has_python_extension = path
+            .extension()
+            .map(|extension| extension == "py" || extension == "pyz")
+            .unwrap_or_default();
+        match exe_type {
+            Some(Binary(BinaryFormat::Pe)) if !cfg!(windows) => Err(error.using_message(
+                "a Windows PE binary (with an MZ header), so it can't be executed on this platform",
+            )),
+            Some(exe_type) if exe_type.runs_with_python() || path.is_executable() => Ok(exe_type),
+            _ if has_python_extension => Ok(PythonScript),
+            Some(_) => Err(error.using_message("not executable (and not a Python script)")),
+            None => Err(error.using_message(
+                "not a script (no #! shebang) or a recognized binary (ELF, Mach-O, or PE on Windows), \
+                so it can't be executed; if it's a Python script, add a #!/usr/bin/env python shebang",
+            )),
+        }
+    }
+    
+    /// If this is run by passing it to `python`, rather than being executed itself.
+    fn runs_with_python(&self) -> bool {
+        match self {
+            Binary(_) | Script => false,
+            PythonScript | Zipapp { .. } | MainDir => true,
+        }
+    }
+}
+
+impl Display for Argv0ProgramType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Binary(format) => write!(f, "{} binary", format),
+            PythonScript => write!(f, "Python script"),
+            Script => write!(f, "script"),
+            Zipapp { .. } => write!(f, "zipapp"),
+            MainDir => write!(f, "__main__ directory"),
+        }
+    }
+}
+
+fn is_zip_magic(magic: &[u8; 4]) -> bool {
+    magic == b"PK\x03\x04"
+}
+
+/// The name of the `python` in a shebang line (after the #!), like `python3.9`,
+/// either as the interpreter itself or as the program run by `env`.
+fn shebang_python_name(shebang: &str) -> Option<String> {
+    let mut words = shebang.split_whitespace();
+    let mut program = words.next()?;
+    if Path::new(program).file_name()? == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    let name = Path::new(program).file_name()?.to_str()?;
+    Some(name.to_string()).filter(|name| name.starts_with("python"))
+}
+
+impl Argv0Chain {
+    /// The most symlinks followed, the same as Linux's limit before `ELOOP`.
+    const MAX_LINKS: usize = 40;
+    
+    /// Resolve `argv0` to a path and then follow its symlinks.
+    ///
+    /// If `argv0` can't be resolved or the chain doesn't end at the current executable
+    /// (e.g. it was run with a custom arg0), the chain is empty.
+    fn new(argv0: &OsStr) -> Self {
+        let mut links = Vec::new();
+        let mut next = Self::resolve(argv0);
+        while let Some(link) = next {
+            if links.len() == Self::MAX_LINKS {
+                return Self::default();
+            }
+            next = fs::read_link(link.as_path())
+                .ok()
+                .map(|target| link
+                    .parent()
+                    .map(|dir| dir.join(target.as_path()))
+                    .unwrap_or(target)
+                );
+            links.push(link);
+        }
+        let current_exe = match env::current_exe() {
+            Ok(current_exe) => current_exe,
+            Err(_) => return Self::default(),
+        };
+        let ends_at_current_exe = links
+            .last()
+            .and_then(|last| same_file::is_same_file(last, current_exe.as_path()).ok())
+            .unwrap_or_default();
+        if !ends_at_current_exe {
+            return Self::default();
+        }
+        Self {
+            links,
+            exe_name: current_exe.file_name().map(|name| name.to_os_string()),
+        }
+    }
+    
+    /// Resolve `argv0` like the shell did: relative to the current directory if it's a path,
+    /// or else through `$PATH` if it's a bare name.
+    fn resolve(argv0: &OsStr) -> Option<PathBuf> {
+        let argv0 = Path::new(argv0);
+        if argv0.components().count() > 1 {
+            return Some(env::current_dir().ok()?.join(argv0));
+        }
+        let path_var = env::var_os("PATH")?;
+        env::split_paths(&path_var)
+            .map(|dir| dir.join(argv0))
+            .find(|path| path.is_executable())
+    }
+    
+    /// The name of the program to run,
+    /// i.e. the last name in the chain before the current executable.
+    ///
+    /// The current executable is reached at the first link with the same name as it,
+    /// since the links after that are usually just where it's installed,
+    /// like `$CARGO_HOME/bin/python`.
+    fn program_name(&self) -> Option<&OsStr> {
+        let index = self.links
+            .iter()
+            .position(|link| link.file_name() == self.exe_name.as_deref())
+            .unwrap_or(self.links.len())
+            .saturating_sub(1);
+        self.links.get(index)?.file_name()
+    }
+}
+
+impl Display for Argv0Chain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, link) in self.links.iter().enumerate() {
+            if i != 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{}", link.display())?;
+        }
+        Ok(())
     }
 }
 
 impl Argv0Program {
     fn new(python_path: PathBuf) -> Result<Self, Argv0ProgramError> {
+        let argv0 = env::args_os().next();
+        let chain = argv0
+            .as_deref()
+            .map(Argv0Chain::new)
+            .unwrap_or_default();
+        let argv0_name = chain
+            .program_name()
+            .or_else(|| Path::new(argv0.as_deref()?).file_name())
+            .map(|name| name.to_os_string());
         let symlinked_path = || -> Option<PathBuf> {
-            let argv0 = env::args_os().next()?;
-            let argv0_name = Path::new(argv0.as_os_str()).file_name()?;
-            let path_buf = python_path.parent()?.join(Path::new(argv0_name));
+            let path_buf = python_path.parent()?.join(Path::new(argv0_name.as_deref()?));
             Some(path_buf)
         };
-        let path = symlinked_path()
+        let mut path = symlinked_path()
             .unwrap_or_else(|| python_path.to_path_buf());
+        if !path.exists() {
+            // zipapps can be run without their `.pyz` extension
+            let mut zipapp_path = path.clone().into_os_string();
+            zipapp_path.push(".pyz");
+            let zipapp_path = PathBuf::from(zipapp_path);
+            if zipapp_path.exists() {
+                trace!("using zipapp {} since {} doesn't exist", zipapp_path.display(), path.display());
+                path = zipapp_path;
+            } else if let Some(app) = argv0_name.as_deref().and_then(Self::find_pipx_app) {
+                trace!("using {} since {} doesn't exist", app, path.display());
+                return Self::from_pipx_app(app, chain);
+            }
+        }
+        trace!("argv0 is {}, invoked via {}", argv0_name.as_deref().unwrap_or_default().to_string_lossy(), chain);
         let exe_type = Argv0ProgramType::detect(path.as_path())?;
+        trace!("argv0 program {} detected as: {}", path.display(), exe_type);
+        let python_path = match &exe_type {
+            Zipapp { python_name: Some(python_name) } => Self::shebang_python(python_path, python_name),
+            _ => python_path,
+        };
         Ok(Self {
             python_path,
             path,
             exe_type,
+            source: Argv0ProgramSource::Python,
+            chain,
+        })
+    }
+    
+    /// Honor the `python` version named in a zipapp's shebang, like `python3.9`,
+    /// if there is such a `python` next to the current `python`.
+    fn shebang_python(python_path: PathBuf, python_name: &str) -> PathBuf {
+        python_path
+            .parent()
+            .map(|dir| dir.join(python_name))
+            .filter(|path| path.is_executable())
+            .unwrap_or(python_path)
+    }
+    
+    /// Look for `name` in the `pipx` venvs if `$PYENV_PYTHON_PIPX` is set,
+    /// for when the current `python` doesn't have it.
+    fn find_pipx_app(name: &OsStr) -> Option<PipxApp> {
+        if !config::flag("PIPX", false) {
+            return None;
+        }
+        PipxHome::new().ok()?.find_app(name)
+    }
+    
+    /// Run a `pipx` app with its own venv's `python` rather than the current `python`.
+    fn from_pipx_app(app: PipxApp, chain: Argv0Chain) -> Result<Self, Argv0ProgramError> {
+        let path = app.path().to_path_buf();
+        let exe_type = Argv0ProgramType::detect(path.as_path())?;
+        trace!("argv0 program {} detected as: {}", path.display(), exe_type);
+        Ok(Self {
+            python_path: app.python_path(),
+            path,
+            exe_type,
+            source: Argv0ProgramSource::Pipx(app),
+            chain,
         })
     }
     
@@ -168,32 +489,267 @@ impl Argv0Program {
             python_path,
             path,
             exe_type,
+            source: _,
+            chain: _,
         } = self;
         match exe_type {
-            Binary => path,
+            Binary(_) => path,
             PythonScript => python_path,
             Script => path,
+            Zipapp { .. } => python_path,
+            MainDir => python_path,
         }.as_path()
     }
     
-    /// The python script path, if it's valid.
+    /// The python script (or zipapp or `__main__` directory) path, if it's valid.
     fn python_script(&self) -> Option<&Path> {
         Some(self.path.as_path())
-            .filter(|_| self.exe_type == PythonScript)
+            .filter(|_| self.exe_type.runs_with_python())
     }
     
-    fn to_command(&self) -> Command {
-        let mut args = env::args_os();
+    /// The command to run this program with the current args (and `env_changes`).
+    ///
+    /// The absolute [`Argv0Program::argv0`] path is passed as arg0,
+    /// so that `python` sets `sys.executable` to the real `python`
+    /// rather than searching `$PATH` for our `python` again.
+    /// Set `$PYENV_PYTHON_ABSOLUTE_ARG0=0` to pass the original arg0 instead.
+    ///
+    /// `args` are the original args, including the original arg0 (usually [`env::args_os`]).
+    fn to_command(&self, mut args: impl Iterator<Item = OsString>, env_changes: &EnvChanges) -> Command {
         let mut cmd = Command::new(self.argv0());
-        if let Some(arg0) = args.next() {
+        if let Some(arg0) = Self::arg0(args.next()) {
             cmd.arg0(arg0);
         }
         if let Some(script) = self.python_script() {
             cmd.arg(script.as_os_str());
         }
         cmd.args(args);
+        env_changes.apply(&mut cmd);
         cmd
     }
+    
+    /// The arg0 to pass instead of the absolute [`Argv0Program::argv0`] path, if any
+    /// (see [`Argv0Program::to_command`]).
+    fn arg0(original_arg0: Option<OsString>) -> Option<OsString> {
+        original_arg0.filter(|_| !config::flag("ABSOLUTE_ARG0", true))
+    }
+}
+
+/// How to handle inherited Python variables (see [`EnvChanges::ISOLATED_VARS`])
+/// that point outside of the `python` being run, set by `$PYENV_PYTHON_ISOLATE`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum IsolateMode {
+    /// Leave them alone (the default).
+    Off,
+    /// Warn about them on stderr (`warn`).
+    Warn,
+    /// Remove them from the environment (`remove`, or any true value like `1`).
+    Remove,
+}
+
+impl IsolateMode {
+    fn from_env() -> Self {
+        match config::value("ISOLATE").as_deref() {
+            Some("warn") => Self::Warn,
+            Some("remove") => Self::Remove,
+            _ if config::flag("ISOLATE", false) => Self::Remove,
+            _ => Self::Off,
+        }
+    }
+}
+
+/// Changes to the environment of the [`Argv0Program`]
+/// (on top of the current environment), applied in order.
+#[derive(Debug, Default)]
+struct EnvChanges {
+    changes: Vec<(OsString, Option<OsString>)>,
+    /// The env files applied and the variables that were set from each.
+    env_files: Vec<(PathBuf, Vec<(String, String)>)>,
+}
+
+impl EnvChanges {
+    /// The variables that can break `python`,
+    /// e.g. with "Fatal Python error: init_fs_encoding",
+    /// if they're left over from a different `python`.
+    const ISOLATED_VARS: &'static [&'static str] = &[
+        "PYTHONHOME",
+        "PYTHONPATH",
+        "PYTHONSTARTUP",
+        "PYTHONUSERBASE",
+        "PYTHONEXECUTABLE",
+    ];
+    
+    /// Determine the environment changes for running `program` with `python`.
+    ///
+    /// First, if `$PYENV_PYTHON_ISOLATE` is set (see [`IsolateMode`]),
+    /// any of the [`EnvChanges::ISOLATED_VARS`] with absolute paths outside of the `python`'s prefix
+    /// are warned about or removed.
+    ///
+    /// If `python` is a `pyenv` `python` (and `program` is from its directory),
+    /// that directory is prepended to `$PATH` like `pyenv exec` does,
+    /// so that subprocesses run the same `python` and scripts.
+    /// Set `$PYENV_PYTHON_PREPEND_PATH=0` to disable this.
+    ///
+    /// If `python` is a `pyenv` `python`, its version is also exported as `$PYENV_VERSION`,
+    /// so that nested invocations use the same version even if they `cd` elsewhere.
+    /// Set `$PYENV_PYTHON_EXPORT_VERSION=0` to disable this.
+    ///
+    /// Then, if `program` is from the `pyenv` `python`'s directory (not a `pipx` app),
+    /// the variables in the `$PYENV_ROOT/.pyenv-python.env` env file are set,
+    /// followed by the ones in the version's own `$PYENV_ROOT/versions/$version/.pyenv-python.env`.
+    ///
+    /// If `$PYENV_PYTHON_DOTENV` is set, the project's `.env` file
+    /// (next to the local `.python-version`) is loaded last,
+    /// but without overriding any variables already set unless `$PYENV_PYTHON_DOTENV_OVERRIDE` is set.
+    ///
+    /// See [`EnvFileError`] for possible errors in reading the env files.
+    fn new(python: &Python, program: &Argv0Program) -> Result<Self, EnvFileError> {
+        let mut changes = Self::default();
+        let isolate_mode = IsolateMode::from_env();
+        if isolate_mode != IsolateMode::Off {
+            match PythonPrefix::find(program.python_path()) {
+                Ok(prefix) => changes.isolate(prefix.prefix(), isolate_mode),
+                Err(e) => eprintln!("pyenv-python: warning: not isolating python from inherited variables: {}", e),
+            }
+        }
+        let is_pyenv_program = matches!(
+            (python, program.source()),
+            (Python::Pyenv(_), Argv0ProgramSource::Python)
+        );
+        if is_pyenv_program && config::flag("PREPEND_PATH", true) {
+            if let Some(dir) = program.python_path().parent() {
+                changes.prepend_path(dir);
+            }
+        }
+        if let Python::Pyenv(pyenv) = python {
+            if config::flag("EXPORT_VERSION", true) {
+                changes.set("PYENV_VERSION", pyenv.version().name());
+            }
+            if let Some(virtualenv) = pyenv.virtualenv().filter(|_| is_pyenv_program) {
+                changes.activate(virtualenv);
+            }
+            // a `pipx` app runs with its own venv's `python`, which the version's env files aren't for
+            if is_pyenv_program {
+                let root = pyenv.root();
+                for dir in [root.path().to_path_buf(), root.version_dir(pyenv.version())].iter() {
+                    changes.apply_env_file(dir.join(".pyenv-python.env"), true)?;
+                }
+            }
+            let project_dir = pyenv.version().project_dir();
+            if let Some(project_dir) = project_dir.filter(|_| config::flag("DOTENV", false)) {
+                let dotenv_override = config::flag("DOTENV_OVERRIDE", false);
+                changes.apply_env_file(project_dir.join(".env"), dotenv_override)?;
+            }
+        }
+        Ok(changes)
+    }
+    
+    /// Set the variables in the env file at `path`, if it exists,
+    /// only overriding variables that are already set if `override_vars`.
+    fn apply_env_file(&mut self, path: PathBuf, override_vars: bool) -> Result<(), EnvFileError> {
+        let lookup = |name: &str| self
+            .get(name)
+            .map(|value| value.to_string_lossy().into_owned());
+        let env_file = match EnvFile::read(path, &lookup)? {
+            None => return Ok(()),
+            Some(env_file) => env_file,
+        };
+        let mut applied = Vec::new();
+        for (key, value) in env_file.vars() {
+            if override_vars || self.get(key).is_none() {
+                self.set(key, value);
+                applied.push((key.clone(), value.clone()));
+            }
+        }
+        self.env_files.push((env_file.path().to_path_buf(), applied));
+        Ok(())
+    }
+    
+    /// The current value of `key`, including any changes so far.
+    fn get(&self, key: &str) -> Option<OsString> {
+        match self.changes.iter().rev().find(|(k, _)| k == key) {
+            Some((_, value)) => value.clone(),
+            None => env::var_os(key),
+        }
+    }
+    
+    fn set(&mut self, key: impl Into<OsString>, value: impl Into<OsString>) {
+        self.changes.push((key.into(), Some(value.into())));
+    }
+    
+    fn remove(&mut self, key: impl Into<OsString>) {
+        self.changes.push((key.into(), None));
+    }
+    
+    /// Warn about or remove (depending on `mode`) any inherited [`EnvChanges::ISOLATED_VARS`]
+    /// with absolute paths outside of `prefix`.
+    fn isolate(&mut self, prefix: &Path, mode: IsolateMode) {
+        let prefix = prefix.canonicalize().unwrap_or_else(|_| prefix.to_path_buf());
+        for &key in Self::ISOLATED_VARS {
+            let value = match env::var_os(key) {
+                Some(value) => value,
+                None => continue,
+            };
+            let is_outside = env::split_paths(&value)
+                .filter(|path| path.is_absolute())
+                .any(|path| !path
+                    .canonicalize()
+                    .unwrap_or(path)
+                    .starts_with(prefix.as_path())
+                );
+            if !is_outside {
+                continue;
+            }
+            match mode {
+                IsolateMode::Off => {}
+                IsolateMode::Warn => eprintln!(
+                    "pyenv-python: warning: ${}={} points outside of {}",
+                    key,
+                    value.to_string_lossy(),
+                    prefix.display(),
+                ),
+                IsolateMode::Remove => self.remove(key),
+            }
+        }
+    }
+    
+    /// Set the same variables as a virtualenv's `activate` script
+    /// (besides `$PATH`, which is already prepended).
+    fn activate(&mut self, virtualenv: &Virtualenv) {
+        self.set("VIRTUAL_ENV", virtualenv.path());
+        self.set("VIRTUAL_ENV_PROMPT", virtualenv.prompt());
+        self.remove("PYTHONHOME");
+    }
+    
+    /// Prepend `dir` to `$PATH`, unless it's already first.
+    fn prepend_path(&mut self, dir: &Path) {
+        let path_var = self.get("PATH").unwrap_or_default();
+        let paths = env::split_paths(&path_var)
+            .take_while(|_| !path_var.is_empty())
+            .collect::<Vec<_>>();
+        if paths.first().map(|first| first == dir).unwrap_or_default() {
+            return;
+        }
+        let new_paths = Some(dir.to_path_buf())
+            .into_iter()
+            .chain(paths);
+        if let Ok(new_path_var) = env::join_paths(new_paths) {
+            self.set("PATH", new_path_var);
+        }
+    }
+    
+    fn env_files(&self) -> &[(PathBuf, Vec<(String, String)>)] {
+        self.env_files.as_slice()
+    }
+    
+    fn apply(&self, cmd: &mut Command) {
+        for (key, value) in &self.changes {
+            match value {
+                Some(value) => cmd.env(key, value),
+                None => cmd.env_remove(key),
+            };
+        }
+    }
 }
 
 impl Display for Argv0Program {
@@ -204,7 +760,7 @@ impl Display for Argv0Program {
         // let [file_name, python_name] = [self.path(), self.python_path()]
         //     .map(|path| path.file_name().unwrap().apply(Path::new));
         let is_python = file_name == python_name;
-        if is_python || self.exe_type == PythonScript {
+        if is_python || self.exe_type.runs_with_python() {
             write!(f, "{}", python_name.display())?;
             if !is_python {
                 write!(f, " ")?;
@@ -217,6 +773,150 @@ impl Display for Argv0Program {
     }
 }
 
+/// The programs exec'd so far by this process (as the same pid),
+/// along with how deeply nested we are overall,
+/// passed on in `$__PYENV_PYTHON_EXEC_CHAIN` to detect exec loops,
+/// e.g. between us and other shims that run `python` again.
+///
+/// Each program is recorded with a hash of its arguments,
+/// since a `python` legitimately re-exec'ing `python` (through us) usually passes different arguments,
+/// but a shim looping back to us doesn't make any progress.
+#[derive(Debug)]
+struct ExecChain {
+    depth: usize,
+    programs: Vec<ExecChainEntry>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct ExecChainEntry {
+    args_hash: u64,
+    program: PathBuf,
+}
+
+impl ExecChainEntry {
+    fn new(program: &Path, args: &[OsString]) -> Self {
+        Self {
+            args_hash: Self::hash_args(args),
+            program: program.to_path_buf(),
+        }
+    }
+    
+    /// A 64-bit FNV-1a hash of the `args`, each followed by a `\0` (which can't be in an arg).
+    ///
+    /// Unlike [`std::collections::hash_map::DefaultHasher`], this is stable across Rust releases,
+    /// which matters since it's compared with hashes from the environment.
+    fn hash_args(args: &[OsString]) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        const PRIME: u64 = 0x100000001b3;
+        args
+            .iter()
+            .flat_map(|arg| arg.to_string_lossy().into_owned().into_bytes().into_iter().chain(Some(0)))
+            .fold(OFFSET_BASIS, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(PRIME))
+    }
+    
+    /// Parses an `<args hash>=<program>` entry.
+    fn parse(entry: PathBuf) -> Option<Self> {
+        let entry = entry.into_os_string().into_string().ok()?;
+        let (args_hash, program) = entry.split_at(entry.find('=')?);
+        Some(Self {
+            args_hash: u64::from_str_radix(args_hash, 16).ok()?,
+            program: PathBuf::from(&program[1..]),
+        })
+    }
+    
+    fn to_path_buf(&self) -> PathBuf {
+        let mut entry = OsString::from(format!("{:x}=", self.args_hash));
+        entry.push(self.program.as_os_str());
+        entry.into()
+    }
+}
+
+#[derive(Debug, Error)]
+enum ExecLoopError {
+    #[error("exec loop detected, a shim is probably running this python again with the same arguments: {}", display_chain(.programs))]
+    Loop { programs: Vec<ExecChainEntry> },
+    #[error("python is nested too deeply ({depth} levels), a shim is probably running this python again")]
+    TooDeep { depth: usize },
+}
+
+fn display_chain(programs: &[ExecChainEntry]) -> String {
+    programs
+        .iter()
+        .map(|entry| entry.program.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+impl ExecChain {
+    const VAR: &'static str = "__PYENV_PYTHON_EXEC_CHAIN";
+    
+    /// Even legitimately nested `python`s (like `tox` running `pip`) shouldn't get this deep.
+    const MAX_DEPTH: usize = 64;
+    
+    /// Read the exec chain from the environment.
+    fn current() -> Self {
+        Self::parse(env::var_os(Self::VAR).unwrap_or_default().as_os_str(), std::process::id())
+    }
+    
+    /// Parse the exec chain from `var` for the process `pid`.
+    ///
+    /// If it's from a different process, then we weren't exec'd by ourselves,
+    /// so only the depth carries over.
+    fn parse(var: &OsStr, pid: u32) -> Self {
+        let mut entries = env::split_paths(var);
+        let var_pid = entries.next().and_then(|pid| pid.to_str()?.parse::<u32>().ok());
+        let depth = entries
+            .next()
+            .and_then(|depth| depth.to_str()?.parse().ok())
+            .unwrap_or_default();
+        let programs = match var_pid {
+            Some(var_pid) if var_pid == pid => entries.filter_map(ExecChainEntry::parse).collect(),
+            _ => Vec::new(),
+        };
+        Self {
+            depth,
+            programs,
+        }
+    }
+    
+    /// Add the `program` about to be exec'd with `args` to the chain,
+    /// or return an [`ExecLoopError`] if it's already been exec'd with the same `args` by this process
+    /// or if we're nested too deeply.
+    fn push(mut self, program: &Path, args: &[OsString]) -> Result<Self, ExecLoopError> {
+        use ExecLoopError::*;
+        let entry = ExecChainEntry::new(program, args);
+        let is_loop = self.programs.contains(&entry);
+        self.programs.push(entry);
+        self.depth += 1;
+        if is_loop {
+            return Err(Loop { programs: self.programs });
+        }
+        if self.depth > Self::MAX_DEPTH {
+            return Err(TooDeep { depth: self.depth });
+        }
+        Ok(self)
+    }
+    
+    /// Pass the chain on to the exec'd program.
+    fn export(&self, env_changes: &mut EnvChanges) {
+        // if a path can't be joined (e.g. it contains a `:`), then we just can't detect loops
+        if let Some(var) = self.to_var(std::process::id()) {
+            env_changes.set(Self::VAR, var);
+        }
+    }
+    
+    /// The value of `$__PYENV_PYTHON_EXEC_CHAIN` for the process `pid`,
+    /// or [`None`] if a program's path can't be joined.
+    fn to_var(&self, pid: u32) -> Option<OsString> {
+        let entries = [pid.to_string(), self.depth.to_string()]
+            .iter()
+            .map(PathBuf::from)
+            .chain(self.programs.iter().map(ExecChainEntry::to_path_buf))
+            .collect::<Vec<_>>();
+        env::join_paths(entries).ok()
+    }
+}
+
 /// Most of the same extension methods as [`std::os::unix::process::CommandExt`],
 /// except this is also implemented on `cfg(not(unix))`,
 /// either with a fallback (`exec`) or not at all (`arg0`).
@@ -254,49 +954,1085 @@ impl CommandExt2 for Command {
     }
 }
 
-/// Run the current `python` (as determined by `pyenv`) with the given args.
-/// If --path is the only arg, print `python`'s path.
-/// If --prefix is the only arg, print `python`'s directory,
-/// the same as `python -c 'import sys; print(sys.prefix)'`.
+/// The `pyenv` version isn't installed, like `pyenv` reports it.
+#[derive(Debug, Error)]
+#[error("version `{name}' is not installed (set by {set_by})")]
+struct VersionNotInstalledError {
+    name: String,
+    set_by: String,
+}
+
+impl VersionNotInstalledError {
+    fn new(version: &PyenvVersion) -> Self {
+        Self {
+            name: version.name().to_string(),
+            set_by: version.set_by(),
+        }
+    }
+}
+
+/// The `--version-*` commands, which print the `pyenv` version like `pyenv` does,
+/// but without looking up or running `python`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum VersionCommand {
+    /// `--version-name`, like `pyenv version-name`.
+    Name,
+    /// `--version-origin`, like `pyenv version-origin`.
+    Origin,
+    /// `--version-file`, like `pyenv version-file`.
+    File,
+}
+
+impl VersionCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let root = PyenvRoot::new()?;
+        match self {
+            Self::Name => match root.version() {
+                None => println!("system"),
+                Some(version) => {
+                    if version.name() != "system" && !root.version_dir(&version).exists() {
+                        return Err(VersionNotInstalledError::new(&version).into());
+                    }
+                    println!("{}", version.name());
+                }
+            },
+            Self::Origin => match root.version() {
+                Some(version) => println!("{}", version.set_by()),
+                None => println_bytes(root.version_file().as_path()),
+            },
+            Self::File => println_bytes(root.version_file().as_path()),
+        }
+        Ok(())
+    }
+}
+
+/// Print the `--prompt` `template` for the `pyenv` version,
+/// or nothing if there's no project version (see [`PromptTemplate::render`]).
+/// Like the `--version-*` commands, this doesn't look up or run `python`.
+fn print_prompt(template: &str) -> anyhow::Result<()> {
+    let template = PromptTemplate::parse(template)?;
+    let root = match PyenvRoot::new() {
+        Ok(root) => root,
+        Err(_) => return Ok(()),
+    };
+    if let Some(prompt) = root
+        .version()
+        .and_then(|version| template.render(&root, &version)) {
+        println!("{}", prompt);
+    }
+    Ok(())
+}
+
+/// Quote `arg` for a POSIX shell if it has any special characters, like Python's `shlex.quote`.
+/// Non-UTF-8 args are converted lossily.
+fn shell_quote(arg: &OsStr) -> String {
+    let arg = arg.to_string_lossy();
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./-_".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.into_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\"'\"'"))
+    }
+}
+
+/// Print the command that would be exec'd for `--print-command`, shell-quoted so it can be re-run,
+/// i.e. `env` with the environment changes, followed by the program and its args.
+/// If arg0 isn't the program path (see [`Argv0Program::arg0`]), it's printed in a comment first.
+fn print_command(program: &Argv0Program, env_changes: &EnvChanges, args: &[OsString]) {
+    let arg0 = Argv0Program::arg0(args.first().cloned());
+    let cmd = program.to_command(args.iter().cloned(), env_changes);
+    if let Some(arg0) = arg0 {
+        println!("# arg0: {}", shell_quote(arg0.as_os_str()));
+    }
+    println!("{}", command_line(&cmd));
+}
+
+/// The shell-quoted command line for `cmd`, using `env` for its environment changes.
+fn command_line(cmd: &Command) -> String {
+    let mut words = Vec::new();
+    if cmd.get_envs().next().is_some() {
+        words.push("env".to_string());
+    }
+    // `env` needs its `-u` options before any assignments
+    for (key, _) in cmd.get_envs().filter(|(_, value)| value.is_none()) {
+        words.push("-u".to_string());
+        words.push(shell_quote(key));
+    }
+    for (key, value) in cmd.get_envs() {
+        if let Some(value) = value {
+            let mut var = key.to_os_string();
+            var.push("=");
+            var.push(value);
+            words.push(shell_quote(var.as_os_str()));
+        }
+    }
+    words.push(shell_quote(cmd.get_program()));
+    words.extend(cmd.get_args().map(shell_quote));
+    words.join(" ")
+}
+
+/// The `--versions --json` output.
+///
+/// Like [`WhichJson`], the schema is documented in the README, and is only ever added to,
+/// unless the `schema` version (which is separate from [`WhichJson`]'s) is incremented.
+#[derive(Debug, Serialize)]
+struct VersionsJson<'a> {
+    schema: u32,
+    versions: Vec<VersionJson<'a>>,
+}
+
+impl VersionsJson<'_> {
+    const SCHEMA: u32 = 1;
+}
+
+#[derive(Debug, Serialize)]
+struct VersionJson<'a> {
+    name: &'a str,
+    path: &'a Path,
+    /// `python`, `virtualenv`, or `conda` (see [`InstalledVersionKind`]).
+    kind: String,
+    alias_of: Option<&'a Path>,
+    broken: Option<String>,
+    current: bool,
+    /// Like in [`WhichPythonJson`], but only for the current version.
+    origin: Option<String>,
+    origin_file: Option<&'a Path>,
+}
+
+/// Print the installed `pyenv` versions for `--versions`, like `pyenv versions`,
+/// marking the current one, or as JSON.
+fn print_versions(json: bool) -> anyhow::Result<()> {
+    let root = PyenvRoot::new()?;
+    let versions = root
+        .versions()
+        .with_context(|| format!("couldn't list the versions in {}", root))?;
+    let current = root.version();
+    let is_current = |installed: &InstalledVersion| current
+        .as_ref()
+        .filter(|version| version.name() == installed.name());
+    if json {
+        let versions = versions
+            .iter()
+            .map(|installed| VersionJson {
+                name: installed.name(),
+                path: installed.path(),
+                kind: installed.kind().to_string(),
+                alias_of: installed.alias_of(),
+                broken: installed.broken().map(|error| error.to_string()),
+                current: is_current(installed).is_some(),
+                origin: is_current(installed).map(|version| version.origin().to_string()),
+                origin_file: is_current(installed).and_then(|version| version.file()),
+            })
+            .collect();
+        let versions = VersionsJson {
+            schema: VersionsJson::SCHEMA,
+            versions,
+        };
+        println!("{}", serde_json::to_string_pretty(&versions)?);
+        return Ok(());
+    }
+    for installed in &versions {
+        let mut details = Vec::new();
+        if installed.kind() != InstalledVersionKind::Python {
+            details.push(installed.kind().to_string());
+        }
+        if let Some(error) = installed.broken() {
+            details.push(format!("broken: {}", error));
+        }
+        let current = is_current(installed);
+        if let Some(version) = current {
+            details.push(format!("set by {}", version.set_by()));
+        }
+        print!("{} {}", if current.is_some() { "*" } else { " " }, installed);
+        if let Some(alias_of) = installed.alias_of() {
+            print!(" --> {}", alias_of.display());
+        }
+        if !details.is_empty() {
+            print!(" ({})", details.join(", "));
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// The `--info --json` output, and the `info` in [`WhichJson`].
+#[derive(Debug, Serialize)]
+struct InfoJson<'a> {
+    /// `CPython`, `PyPy`, or `GraalPy`.
+    implementation: String,
+    version: &'a str,
+    /// If the `version` is exact, rather than just `X.Y`.
+    exact: bool,
+    abi_flags: &'a str,
+    debug: bool,
+    free_threaded: bool,
+    prefix: &'a Path,
+    base_prefix: &'a Path,
+    venv: bool,
+}
+
+impl<'a> InfoJson<'a> {
+    fn new(info: &'a PythonInfo) -> Self {
+        Self {
+            implementation: info.implementation().to_string(),
+            version: info.version(),
+            exact: info.is_exact(),
+            abi_flags: info.abi_flags(),
+            debug: info.is_debug(),
+            free_threaded: info.is_free_threaded(),
+            prefix: info.prefix(),
+            base_prefix: info.base_prefix(),
+            venv: info.is_venv(),
+        }
+    }
+}
+
+/// Print the [`PythonInfo`] for `--info`, or as JSON.
+fn print_info(info: &PythonInfo, json: bool) -> anyhow::Result<()> {
+    if json {
+        let info = InfoJson::new(info);
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+    println!("implementation: {}", info.implementation());
+    println!("version: {}{}", info.version(), if info.is_exact() { "" } else { " (inexact)" });
+    println!("abi flags: {}", info.abi_flags());
+    println!("prefix: {}", info.prefix().display());
+    println!("base prefix: {}", info.base_prefix().display());
+    Ok(())
+}
+
+/// The line to print for exactly `python --version` or `python -V`
+/// without running `python`, if `$PYENV_PYTHON_FAST_VERSION` is set,
+/// which is the same as what CPython prints (`Python X.Y.Z`, with any `+` dev marker).
+///
+/// Returns [`None`] to run `python` normally if the program isn't `python` itself,
+/// or if it's not certain what `python` would print, 
+/// i.e. if it's not CPython 3 with an exact version from exactly one `patchlevel.h`
+/// (see [`PythonInfo::is_exact`]).
+/// CPython 2 prints its version to stderr, so it's not answered either.
+fn fast_version(program: &Argv0Program, args: &[OsString]) -> Option<String> {
+    if !config::flag("FAST_VERSION", false) {
+        return None;
+    }
+    let args = args.get(1..).unwrap_or_default();
+    if !(args == ["--version"] || args == ["-V"]) || program.path() != program.python_path() {
+        return None;
+    }
+    let info = PythonInfo::detect(program.python_path()).ok()?;
+    let is_cpython3 = info.implementation() == PythonImplementation::CPython
+        && info.version().split('.').next() == Some("3");
+    if !is_cpython3 || !info.is_exact() {
+        trace!("not answering --version from {}, since it's not exact CPython 3", info);
+        return None;
+    }
+    trace!("answering --version from {} without running it", info);
+    Some(format!("Python {}", info.version()))
+}
+
+/// The `--which --json` output.
+///
+/// The schema is documented in the README, and is only ever added to,
+/// unless the `schema` version is incremented.
+#[derive(Debug, Serialize)]
+struct WhichJson<'a> {
+    schema: u32,
+    python: WhichPythonJson<'a>,
+    program: WhichProgramJson<'a>,
+    env_files: Vec<WhichEnvFileJson<'a>>,
+    /// The [`PythonInfo`] of the program's `python`, if it could be determined.
+    info: Option<InfoJson<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct WhichPythonJson<'a> {
+    /// `pyenv`, `system`, or `fallback` (see [`Python`]).
+    kind: &'static str,
+    path: &'a Path,
+    version: Option<&'a str>,
+    /// `shell`, `local`, or `global` (see `PyenvVersionFrom`).
+    origin: Option<String>,
+    origin_file: Option<&'a Path>,
+    root: Option<&'a Path>,
+    virtualenv: Option<&'a Path>,
+    /// Why the `pyenv` `python` wasn't used if it's a `fallback`.
+    fallback_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct WhichProgramJson<'a> {
+    path: &'a Path,
+    argv0: &'a Path,
+    /// `binary`, `python-script`, `script`, `zipapp`, or `main-dir` (see [`Argv0ProgramType`]).
+    #[serde(rename = "type")]
+    exe_type: &'static str,
+    binary_format: Option<String>,
+    /// `python` or `pipx` (see [`Argv0ProgramSource`]).
+    source: &'static str,
+    pipx_venv: Option<&'a Path>,
+    chain: &'a [PathBuf],
+}
+
+#[derive(Debug, Serialize)]
+struct WhichEnvFileJson<'a> {
+    path: &'a Path,
+    vars: Vec<WhichEnvVarJson<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct WhichEnvVarJson<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+impl<'a> WhichJson<'a> {
+    const SCHEMA: u32 = 1;
+    
+    fn new(
+        python: &'a Python,
+        program: &'a Argv0Program,
+        env_changes: &'a EnvChanges,
+        info: Option<&'a PythonInfo>,
+    ) -> Self {
+        let pyenv = match python {
+            Python::Pyenv(pyenv) => Some(pyenv),
+            _ => None,
+        };
+        let python_json = WhichPythonJson {
+            kind: match python {
+                Python::Pyenv(_) => "pyenv",
+                Python::System(_) => "system",
+                Python::Fallback { .. } => "fallback",
+            },
+            path: python.python().path(),
+            version: pyenv.map(|pyenv| pyenv.version().name()),
+            origin: pyenv.map(|pyenv| pyenv.version().origin().to_string()),
+            origin_file: pyenv.and_then(|pyenv| pyenv.version().file()),
+            root: pyenv.map(|pyenv| pyenv.root().path()),
+            virtualenv: pyenv.and_then(|pyenv| pyenv.virtualenv()).map(|venv| venv.path()),
+            fallback_reason: match python {
+                Python::Fallback { reason, .. } => Some(reason.to_string()),
+                _ => None,
+            },
+        };
+        let program_json = WhichProgramJson {
+            path: program.path(),
+            argv0: program.argv0(),
+            exe_type: match program.exe_type() {
+                Binary(_) => "binary",
+                PythonScript => "python-script",
+                Script => "script",
+                Zipapp { .. } => "zipapp",
+                MainDir => "main-dir",
+            },
+            binary_format: match program.exe_type() {
+                Binary(format) => Some(format.to_string()),
+                _ => None,
+            },
+            source: match program.source() {
+                Argv0ProgramSource::Python => "python",
+                Argv0ProgramSource::Pipx(_) => "pipx",
+            },
+            pipx_venv: match program.source() {
+                Argv0ProgramSource::Python => None,
+                Argv0ProgramSource::Pipx(app) => Some(app.venv()),
+            },
+            chain: program.chain().links.as_slice(),
+        };
+        let env_files = env_changes
+            .env_files()
+            .iter()
+            .map(|(path, vars)| WhichEnvFileJson {
+                path,
+                vars: vars
+                    .iter()
+                    .map(|(name, value)| WhichEnvVarJson { name, value })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            schema: Self::SCHEMA,
+            python: python_json,
+            program: program_json,
+            env_files,
+            info: info.map(InfoJson::new),
+        }
+    }
+}
+
+/// What this `python` wrapper does instead of running the program, set by its [`WrapperOptions`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum WrapperCommand {
+    Help,
+    /// Print the program's path.
+    Path,
+    /// Print the program's directory.
+    Dir,
+    /// Print the program's `python`'s prefix (see [`PythonPrefix`]).
+    Prefix,
+    /// Print the program's `python`'s base prefix, which differs from the prefix in a venv.
+    BasePrefix,
+    Which,
+    Info,
+    Versions,
+    Version(VersionCommand),
+    Prompt,
+    PrintCommand,
+}
+
+/// The options of this `python` wrapper, which come before any of the program's own args.
+///
+/// Each option has a long `--pyenv-python-*` name, and all but `--pyenv-python-help` have a short form,
+/// like `--which` for `--pyenv-python-which`, which doesn't clash with any of `python`'s own options.
+/// Options can be combined, like `--which --json` or `--explain --print-command`,
+/// and a `--` after an option ends the options, so the rest of the args are passed through as is.
+/// A `--` before any option is `python`'s own, so it's passed through, too.
+#[derive(Debug, Default)]
+struct WrapperOptions {
+    command: Option<WrapperCommand>,
+    json: bool,
+    explain: bool,
+    prompt: Option<String>,
+    /// The args to pass through to the program, starting with the original arg0.
+    args: Vec<OsString>,
+}
+
+/// Invalid [`WrapperOptions`].
+#[derive(Debug, Error)]
+enum WrapperOptionsError {
+    #[error("unknown option {option} (see --pyenv-python-help)")]
+    Unknown { option: String },
+    #[error("options {first} and {second} can't be used together")]
+    Conflict { first: String, second: String },
+    #[error("option {option} needs a value")]
+    MissingValue { option: String },
+    #[error("option {option} doesn't take a value")]
+    UnexpectedValue { option: String },
+    #[error("option {option} needs a UTF-8 value")]
+    NonUtf8Value { option: String },
+    #[error("--json can only be used with --which, --info, or --versions")]
+    JsonUnsupported,
+}
+
+impl WrapperOptions {
+    const LONG_PREFIX: &'static str = "--pyenv-python-";
+    
+    const HELP: &'static str = "\
+Usage: python [WRAPPER OPTIONS] [--] [PYTHON ARGS...]
+
+Runs the pyenv python (or the program named by arg0) with PYTHON ARGS.
+The wrapper options come first, and are each --pyenv-python-NAME or just --NAME:
+
+    --path                print the path of the program that will be run
+    --dir                 print the directory of the program that will be run
+    --prefix              print the prefix of the python that will be run, like sys.prefix
+    --base-prefix         print the base prefix of the python that will be run, like sys.base_prefix
+    --which               print what will be run using which python, and why
+    --explain             print each step of looking up python (with --which by default)
+    --info                print the python's implementation, version, ABI flags, and prefix
+    --versions            list the installed pyenv versions
+    --version-name        print the pyenv version name, like `pyenv version-name`
+    --version-origin      print what set the pyenv version, like `pyenv version-origin`
+    --version-file        print the pyenv version file, like `pyenv version-file`
+    --prompt TEMPLATE     print a prompt segment, like '{version} ({origin}){venv: [{venv}]}'
+    --print-command       print the command that would be run with PYTHON ARGS instead of running it
+    --json                print --which, --info, or --versions as JSON (also --which=json, etc.)
+    --pyenv-python-help   print this help
+
+A -- after any wrapper option ends them, so that the rest of the args are passed through as is.
+A -- before any wrapper option is passed through to python itself.
+";
+
+    /// Parses the options from the original `args`, including the original arg0.
+    ///
+    /// See [`WrapperOptionsError`] for possible errors.
+    fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Self, WrapperOptionsError> {
+        use WrapperCommand::*;
+        use WrapperOptionsError::*;
+        let mut args = args.into_iter().peekable();
+        let mut options = Self::default();
+        let mut command_option = String::new();
+        let mut has_options = false;
+        options.args.extend(args.next());
+        while let Some(arg) = args.peek() {
+            let arg = match arg.to_str() {
+                // a leading `--` is `python`'s, not ours
+                Some("--") if !has_options => break,
+                Some("--") => {
+                    args.next();
+                    break;
+                }
+                Some(arg) if arg.starts_with("--") => arg.to_string(),
+                _ => break,
+            };
+            let (option, value) = match arg.find('=') {
+                Some(i) => (&arg[..i], Some(&arg[i + 1..])),
+                None => (arg.as_str(), None),
+            };
+            let is_long = option.starts_with(Self::LONG_PREFIX);
+            let name = option
+                .strip_prefix(Self::LONG_PREFIX)
+                .unwrap_or(&option[2..]);
+            let command = match name {
+                "help" if is_long => Some(Help),
+                "path" => Some(Path),
+                "dir" => Some(Dir),
+                "prefix" => Some(Prefix),
+                "base-prefix" => Some(BasePrefix),
+                "which" => Some(Which),
+                "info" => Some(Info),
+                "versions" => Some(Versions),
+                "version-name" => Some(Version(VersionCommand::Name)),
+                "version-origin" => Some(Version(VersionCommand::Origin)),
+                "version-file" => Some(Version(VersionCommand::File)),
+                "prompt" => Some(Prompt),
+                "print-command" => Some(PrintCommand),
+                "json" | "explain" => None,
+                // not one of our options, so it's `python`'s
+                _ if !is_long => break,
+                _ => return Err(Unknown { option: arg }),
+            };
+            args.next();
+            has_options = true;
+            match (name, value) {
+                ("which" | "info" | "versions", Some("json")) => options.json = true,
+                ("prompt", Some(value)) => options.prompt = Some(value.to_string()),
+                ("prompt", None) => {
+                    let value = args.next().ok_or_else(|| MissingValue {
+                        option: option.to_string(),
+                    })?;
+                    let value = value.into_string().map_err(|_| NonUtf8Value {
+                        option: option.to_string(),
+                    })?;
+                    options.prompt = Some(value);
+                }
+                (_, Some(_)) => return Err(UnexpectedValue {
+                    option: option.to_string(),
+                }),
+                ("json", None) => options.json = true,
+                ("explain", None) => options.explain = true,
+                (_, None) => {}
+            }
+            if let Some(command) = command {
+                if options.command.is_some() {
+                    return Err(Conflict {
+                        first: command_option,
+                        second: option.to_string(),
+                    });
+                }
+                options.command = Some(command);
+                command_option = option.to_string();
+            }
+        }
+        options.args.extend(args);
+        if options.explain && options.command.is_none() {
+            options.command = Some(Which);
+        }
+        if options.json && !matches!(options.command, Some(Which | Info | Versions)) {
+            return Err(JsonUnsupported);
+        }
+        Ok(options)
+    }
+}
+
+/// The exit code for each kind of error, documented in the README.
+///
+/// `127` and `126` are the same as for shells (and `pyenv exec`).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ErrorCode {
+    /// Any other error, like an invalid env file.
+    Other = 1,
+    /// The `pyenv` version isn't installed.
+    NotInstalled = 2,
+    /// No `pyenv` version is set, and falling back to the system `python` isn't allowed.
+    NoVersion = 3,
+    /// The `pyenv` root wasn't found, and falling back to the system `python` isn't allowed.
+    NoRoot = 4,
+    /// This `python` is being run again by itself, e.g. through a shim.
+    ExecLoop = 5,
+    /// The wrapper options are invalid (see [`WrapperOptions`]).
+    Usage = 6,
+    /// The program was found, but can't be executed.
+    CannotExecute = 126,
+    /// The program, or `python` itself, wasn't found.
+    NotFound = 127,
+}
+
+/// The exit code for an `error`, and a `pyenv`-style message for it
+/// (without the `pyenv: ` prefix), or [`None`] if it should be printed in full.
+fn error_code(error: &anyhow::Error) -> (ErrorCode, Option<String>) {
+    use ErrorCode::*;
+    if let Some(error) = error.downcast_ref::<PythonError>() {
+        return match error {
+            PythonError::NoFallback { pyenv, policy } => match pyenv {
+                PyenvError::NoRoot { error } => (NoRoot, Some(format!(
+                    "no pyenv root found ({}) and not falling back to system python with $PYENV_PYTHON_FALLBACK={}",
+                    error, policy,
+                ))),
+                PyenvError::NoVersion { .. } => (NoVersion, Some(format!(
+                    "no version set and not falling back to system python with $PYENV_PYTHON_FALLBACK={}",
+                    policy,
+                ))),
+                PyenvError::NoExecutable { root, version, python_path, .. } => (NotInstalled, Some(
+                    if root.version_dir(version).exists() {
+                        format!(
+                            "version `{}' is missing {} (set by {})",
+                            version.name(), python_path.display(), version.set_by(),
+                        )
+                    } else {
+                        VersionNotInstalledError::new(version).to_string()
+                    }
+                )),
+            },
+            PythonError::NotFound { pyenv, system } => (NotFound, Some(format!(
+                "python: command not found ({}, {})",
+                pyenv, system,
+            ))),
+        };
+    }
+    if let Some(error) = error.downcast_ref::<VersionNotInstalledError>() {
+        return (NotInstalled, Some(error.to_string()));
+    }
+    if let Some(error) = error.downcast_ref::<PyenvRootError>() {
+        return (NoRoot, Some(format!("no pyenv root found ({})", error)));
+    }
+    if let Some(error) = error.downcast_ref::<Argv0ProgramError>() {
+        return if error.is_not_found() {
+            let name = error.path.file_name().unwrap_or(error.path.as_os_str());
+            (NotFound, Some(format!("{}: command not found", name.to_string_lossy())))
+        } else {
+            (CannotExecute, None)
+        };
+    }
+    if let Some(error) = error.downcast_ref::<WrapperOptionsError>() {
+        return (Usage, Some(error.to_string()));
+    }
+    if let Some(error) = error.downcast_ref::<ExecLoopError>() {
+        return (ExecLoop, Some(error.to_string()));
+    }
+    if let Some(error) = error.downcast_ref::<io::Error>() {
+        // from exec'ing the program
+        return match error.kind() {
+            io::ErrorKind::NotFound => (NotFound, None),
+            io::ErrorKind::PermissionDenied => (CannotExecute, None),
+            _ => (Other, None),
+        };
+    }
+    (Other, None)
+}
+
+/// Run the current `python` (as determined by `pyenv`) with the given args,
+/// unless any [`WrapperOptions`] come first, like `--path` or `--which`.
 /// These are the only differences from actual `python`,
 /// and they don't clash with any of `python`'s actual options.
-fn main() -> anyhow::Result<()> {
+///
+/// Errors are printed like `pyenv` does and exit with an [`ErrorCode`].
+fn main() {
+    if let Err(error) = run() {
+        let (code, message) = error_code(&error);
+        match message {
+            Some(message) => eprintln!("pyenv: {}", message),
+            None => eprintln!("Error: {:?}", error),
+        }
+        process::exit(code as i32);
+    }
+}
+
+fn run() -> anyhow::Result<()> {
+    let options = WrapperOptions::parse(env::args_os())?;
+    if options.explain {
+        // keep the JSON output parseable
+        trace::set_destination(if options.json {
+            trace::TraceDestination::Stderr
+        } else {
+            trace::TraceDestination::Stdout
+        });
+    }
+    match options.command {
+        Some(WrapperCommand::Help) => {
+            print!("{}", WrapperOptions::HELP);
+            return Ok(());
+        }
+        Some(WrapperCommand::Version(command)) => return command.run(),
+        Some(WrapperCommand::Prompt) => return print_prompt(options.prompt.as_deref().unwrap_or_default()),
+        Some(WrapperCommand::Versions) => return print_versions(options.json),
+        _ => {}
+    }
     let python = Python::new().context("python not found")?;
+    if let Python::Fallback { reason: reason @ PyenvError::NoExecutable { .. }, .. } = &python {
+        eprintln!("pyenv-python: warning: ignoring pyenv version: {}", reason);
+    }
     let program = python
         .python()
         .path()
         .to_path_buf()
         .apply(Argv0Program::new)?;
-    let parent_level: Option<usize> = match env::args()
-        .nth(1)
-        .unwrap_or_default()
-        .as_str() {
-        "--path" => Some(0),
-        "--dir" => Some(1),
-        "--prefix" => Some(2),
-        "--which" => {
-            println!("`{}` using {}", program, python);
-            return Ok(());
-        }
-        _ => None,
-    };
-    match parent_level {
-        None => program
-            .to_command()
-            .exec()
-            .apply(Err)
-            .context("failed to run python subprocess")?,
-        Some(level) => {
-            let mut dir = program.path();
-            for current_level in 0..level {
-                dir = dir.parent()
-                    .with_context(|| format!(
-                        "python --path doesn't have {} parent directories",
-                        current_level,
-                    ))?;
+    match options.command {
+        None => {
+            if let Some(version) = fast_version(&program, options.args.as_slice()) {
+                println!("{}", version);
+                return Ok(());
             }
+            let mut env_changes = EnvChanges::new(&python, &program)?;
+            trace!("running {}", program.argv0().display());
+            ExecChain::current()
+                .push(program.argv0(), options.args.as_slice())?
+                .export(&mut env_changes);
+            program
+                .to_command(options.args.into_iter(), &env_changes)
+                .exec()
+            .apply(Err)
+            .context("failed to run python subprocess")?
+        }
+        Some(WrapperCommand::PrintCommand) => {
+            let env_changes = EnvChanges::new(&python, &program)?;
+            print_command(&program, &env_changes, options.args.as_slice());
+        }
+        Some(WrapperCommand::Path) => println_bytes(program.path()),
+        Some(WrapperCommand::Dir) => {
+            let dir = program
+                .path()
+                .parent()
+                .with_context(|| format!("{} doesn't have a parent directory", program.path().display()))?;
             println_bytes(dir);
         }
+        Some(command @ (WrapperCommand::Prefix | WrapperCommand::BasePrefix)) => {
+            let prefix = PythonPrefix::find(program.python_path())?;
+            println_bytes(match command {
+                WrapperCommand::Prefix => prefix.prefix(),
+                _ => prefix.base_prefix(),
+            });
+        }
+        Some(WrapperCommand::Info) => {
+            let info = PythonInfo::detect(program.python_path())?;
+            print_info(&info, options.json)?;
+        }
+        Some(WrapperCommand::Which) => {
+            let env_changes = EnvChanges::new(&python, &program)?;
+            let info = PythonInfo::detect(program.python_path()).ok();
+            if options.json {
+                let which = WhichJson::new(&python, &program, &env_changes, info.as_ref());
+                println!("{}", serde_json::to_string_pretty(&which)?);
+                return Ok(());
+            }
+            print!("`{}` ({})", program, program.exe_type());
+            match program.source() {
+                Argv0ProgramSource::Python => print!(" using {}", python),
+                Argv0ProgramSource::Pipx(app) => print!(" using {}", app),
+            }
+            if let Some(info) = &info {
+                print!(" ({})", info);
+            }
+            if program.chain().links.len() > 1 {
+                print!(", invoked via {}", program.chain());
+            }
+            println!();
+            for (env_file, vars) in env_changes.env_files() {
+                println!("with variables from {}:", env_file.display());
+                for (key, value) in vars {
+                    println!("    {}={}", key, value);
+                }
+            }
+        }
+        Some(
+            WrapperCommand::Help
+            | WrapperCommand::Version(_)
+            | WrapperCommand::Prompt
+            | WrapperCommand::Versions
+        ) => unreachable!("handled before looking up python"),
     }
     Ok(())
 }
+
+#[cfg(test)]
+#[path = "../temp_prefix.rs"]
+mod temp_prefix;
+
+#[cfg(test)]
+mod tests {
+    use crate::temp_prefix::{TempPrefix, PATCHLEVEL_3_11, PATCHLEVEL_3_12};
+    
+    use super::*;
+    
+    fn quote(arg: &str) -> String {
+        shell_quote(OsStr::new(arg))
+    }
+    
+    #[test]
+    fn shell_quotes_only_if_needed() {
+        assert_eq!(quote("/usr/bin/python3.9"), "/usr/bin/python3.9");
+        assert_eq!(quote("--opt=a,b:c@d%e+f"), "--opt=a,b:c@d%e+f");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+        assert_eq!(quote("print('hi')"), r#"'print('"'"'hi'"'"')'"#);
+        assert_eq!(quote("*"), "'*'");
+        assert_eq!(quote("a\nb"), "'a\nb'");
+    }
+    
+    #[test]
+    fn command_line_without_env_changes() {
+        let mut cmd = Command::new("/versions/3.9.6/bin/python");
+        cmd.args(["-c", "print('$HOME')", ""].iter());
+        assert_eq!(command_line(&cmd), r#"/versions/3.9.6/bin/python -c 'print('"'"'$HOME'"'"')' ''"#);
+    }
+    
+    #[test]
+    fn command_line_unsets_before_assignments() {
+        let mut cmd = Command::new("/versions/3.9.6/bin/python");
+        cmd.env("A", "1")
+            .env_remove("B")
+            .env("C", "a b")
+            .env_remove("D")
+            .arg("script.py");
+        assert_eq!(command_line(&cmd), "env -u B -u D A=1 'C=a b' /versions/3.9.6/bin/python script.py");
+    }
+    
+    #[test]
+    fn command_line_keeps_assignment_like_args_after_program() {
+        let mut cmd = Command::new("/versions/3.9.6/bin/python");
+        cmd.env("A", "1").args(["-m", "tool", "FOO=bar", "BAR=a b"].iter());
+        assert_eq!(command_line(&cmd), "env A=1 /versions/3.9.6/bin/python -m tool FOO=bar 'BAR=a b'");
+    }
+    
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+    
+    fn empty_chain() -> ExecChain {
+        ExecChain::parse(OsStr::new(""), 1)
+    }
+    
+    #[test]
+    fn exec_chain_round_trips() {
+        let chain = empty_chain()
+            .push(Path::new("/versions/3.9.6/bin/python"), &args(&["python", "-c", "x"]))
+            .unwrap()
+            .push(Path::new("/versions/3.9.6/bin/pytest"), &args(&["pytest"]))
+            .unwrap();
+        let parsed = ExecChain::parse(chain.to_var(1).unwrap().as_os_str(), 1);
+        assert_eq!(parsed.depth, 2);
+        assert_eq!(parsed.programs, chain.programs);
+    }
+    
+    #[test]
+    fn exec_chain_resets_programs_from_other_processes() {
+        let chain = empty_chain()
+            .push(Path::new("/versions/3.9.6/bin/python"), &args(&["python"]))
+            .unwrap();
+        let parsed = ExecChain::parse(chain.to_var(1).unwrap().as_os_str(), 2);
+        assert_eq!(parsed.depth, 1);
+        assert_eq!(parsed.programs, []);
+    }
+    
+    #[test]
+    fn exec_chain_detects_same_program_and_args_as_loop() {
+        let python = Path::new("/versions/3.9.6/bin/python");
+        let result = empty_chain()
+            .push(python, &args(&["python", "-c", "x"]))
+            .unwrap()
+            .push(python, &args(&["python", "-c", "x"]));
+        assert!(matches!(result, Err(ExecLoopError::Loop { .. })));
+    }
+    
+    #[test]
+    fn exec_chain_allows_same_program_with_different_args() {
+        let python = Path::new("/versions/3.9.6/bin/python");
+        let result = empty_chain()
+            .push(python, &args(&["python", "first"]))
+            .unwrap()
+            .push(python, &args(&["python", "-c", "second"]));
+        assert!(result.is_ok());
+        // the args are separated, so they can't be shifted between each other
+        assert_ne!(
+            ExecChainEntry::hash_args(&args(&["ab", "c"])),
+            ExecChainEntry::hash_args(&args(&["a", "bc"])),
+        );
+    }
+    
+    #[test]
+    fn exec_chain_is_too_deep_after_max_depth() {
+        let mut chain = empty_chain();
+        for i in 0..ExecChain::MAX_DEPTH {
+            chain = ExecChain::parse(chain.to_var(1).unwrap().as_os_str(), 2)
+                .push(Path::new("/bin/python"), &args(&[i.to_string().as_str()]))
+                .unwrap();
+        }
+        let result = chain.push(Path::new("/bin/python"), &args(&["last"]));
+        assert!(matches!(result, Err(ExecLoopError::TooDeep { depth }) if depth == ExecChain::MAX_DEPTH + 1));
+    }
+    
+    fn program_name(links: &[&str]) -> Option<String> {
+        let chain = Argv0Chain {
+            links: links.iter().map(PathBuf::from).collect(),
+            exe_name: Some(OsString::from("python")),
+        };
+        chain.program_name().map(|name| name.to_string_lossy().into_owned())
+    }
+    
+    #[test]
+    fn program_name_of_plain_python() {
+        assert_eq!(program_name(&["/cargo/bin/python"]).as_deref(), Some("python"));
+    }
+    
+    #[test]
+    fn program_name_of_python_alias() {
+        assert_eq!(program_name(&["/bin/python3", "/cargo/bin/python"]).as_deref(), Some("python3"));
+    }
+    
+    #[test]
+    fn program_name_is_last_before_python() {
+        let links = ["/home/me/bin/pytest", "/home/me/.local/shim/pytest", "/cargo/bin/python"];
+        assert_eq!(program_name(&links).as_deref(), Some("pytest"));
+    }
+    
+    #[test]
+    fn program_name_stops_at_first_python() {
+        let links = ["/home/me/bin/python", "/home/me/tools/black", "/cargo/bin/python"];
+        assert_eq!(program_name(&links).as_deref(), Some("python"));
+    }
+    
+    #[test]
+    fn program_name_of_empty_chain() {
+        assert_eq!(program_name(&[]), None);
+    }
+    
+    fn python_program(prefix: &TempPrefix, name: &str) -> Argv0Program {
+        Argv0Program {
+            python_path: prefix.path().join("bin/python"),
+            path: prefix.path().join("bin").join(name),
+            exe_type: Binary(BinaryFormat::Elf),
+            source: Argv0ProgramSource::Python,
+            chain: Argv0Chain::default(),
+        }
+    }
+    
+    /// All in one test, since `$PYENV_PYTHON_FAST_VERSION` is shared by the whole process.
+    #[test]
+    fn fast_version_only_answers_exact_version_args_for_python() {
+        let prefix = TempPrefix::new("fast-version", &[
+            ("bin/python", ""),
+            ("bin/pytest", ""),
+            ("lib/python3.11/os.py", ""),
+            ("include/python3.11/patchlevel.h", PATCHLEVEL_3_11),
+            ("include/python3.12/patchlevel.h", PATCHLEVEL_3_12),
+        ]);
+        let python = python_program(&prefix, "python");
+        let fast_version = |program: &Argv0Program, args: &[&str]| {
+            let args = ["python"].iter().chain(args).map(OsString::from).collect::<Vec<_>>();
+            fast_version(program, args.as_slice())
+        };
+        
+        env::remove_var("PYENV_PYTHON_FAST_VERSION");
+        assert_eq!(fast_version(&python, &["--version"]), None);
+        env::set_var("PYENV_PYTHON_FAST_VERSION", "0");
+        assert_eq!(fast_version(&python, &["--version"]), None);
+        
+        env::set_var("PYENV_PYTHON_FAST_VERSION", "1");
+        assert_eq!(fast_version(&python, &["--version"]).as_deref(), Some("Python 3.11.4"));
+        assert_eq!(fast_version(&python, &["-V"]).as_deref(), Some("Python 3.11.4"));
+        assert_eq!(fast_version(&python, &["-VV"]), None);
+        assert_eq!(fast_version(&python, &["--version", "extra"]), None);
+        assert_eq!(fast_version(&python, &["-c", "--version"]), None);
+        assert_eq!(fast_version(&python, &[]), None);
+        assert_eq!(fast_version(&python_program(&prefix, "pytest"), &["--version"]), None);
+        
+        let dev_prefix = TempPrefix::new("fast-version-dev", &[
+            ("bin/python", ""),
+            ("lib/python3.13/os.py", ""),
+            ("include/python3.13/patchlevel.h", "#define PY_VERSION \"3.13.0a1+\"\n"),
+        ]);
+        let dev_python = python_program(&dev_prefix, "python");
+        assert_eq!(fast_version(&dev_python, &["-V"]).as_deref(), Some("Python 3.13.0a1+"));
+        env::remove_var("PYENV_PYTHON_FAST_VERSION");
+    }
+    
+    fn parse(args: &[&str]) -> Result<WrapperOptions, WrapperOptionsError> {
+        WrapperOptions::parse(["python"].iter().chain(args).map(OsString::from))
+    }
+    
+    fn passed_args(options: &WrapperOptions) -> Vec<&str> {
+        options.args.iter().skip(1).map(|arg| arg.to_str().unwrap()).collect()
+    }
+    
+    #[test]
+    fn passes_python_args_through() {
+        let options = parse(&["-c", "print(1)", "--which"]).unwrap();
+        assert_eq!(options.command, None);
+        assert_eq!(options.args[0], "python");
+        assert_eq!(passed_args(&options), ["-c", "print(1)", "--which"]);
+    }
+    
+    #[test]
+    fn parses_short_and_long_names() {
+        assert_eq!(parse(&["--which"]).unwrap().command, Some(WrapperCommand::Which));
+        assert_eq!(parse(&["--pyenv-python-which"]).unwrap().command, Some(WrapperCommand::Which));
+        assert_eq!(parse(&["--pyenv-python-help"]).unwrap().command, Some(WrapperCommand::Help));
+        assert_eq!(
+            parse(&["--version-file"]).unwrap().command,
+            Some(WrapperCommand::Version(VersionCommand::File)),
+        );
+    }
+    
+    #[test]
+    fn passes_unknown_short_options_through() {
+        let options = parse(&["--help"]).unwrap();
+        assert_eq!(options.command, None);
+        assert_eq!(passed_args(&options), ["--help"]);
+    }
+    
+    #[test]
+    fn combines_options() {
+        let options = parse(&["--explain", "--print-command", "-c", "x"]).unwrap();
+        assert_eq!(options.command, Some(WrapperCommand::PrintCommand));
+        assert!(options.explain);
+        assert_eq!(passed_args(&options), ["-c", "x"]);
+        let options = parse(&["--info=json"]).unwrap();
+        assert_eq!(options.command, Some(WrapperCommand::Info));
+        assert!(options.json);
+        let options = parse(&["--json", "--versions"]).unwrap();
+        assert_eq!(options.command, Some(WrapperCommand::Versions));
+        assert!(options.json);
+    }
+    
+    #[test]
+    fn explain_defaults_to_which() {
+        let options = parse(&["--explain"]).unwrap();
+        assert_eq!(options.command, Some(WrapperCommand::Which));
+        assert!(options.explain);
+    }
+    
+    #[test]
+    fn parses_prompt_values() {
+        let options = parse(&["--prompt", "{version}"]).unwrap();
+        assert_eq!(options.command, Some(WrapperCommand::Prompt));
+        assert_eq!(options.prompt.as_deref(), Some("{version}"));
+        assert_eq!(parse(&["--prompt={origin}"]).unwrap().prompt.as_deref(), Some("{origin}"));
+    }
+    
+    #[test]
+    fn only_ends_options_with_dashes_after_an_option() {
+        let options = parse(&["--print-command", "--", "--which"]).unwrap();
+        assert_eq!(options.command, Some(WrapperCommand::PrintCommand));
+        assert_eq!(passed_args(&options), ["--which"]);
+        let options = parse(&["--", "-c", "x"]).unwrap();
+        assert_eq!(options.command, None);
+        assert_eq!(passed_args(&options), ["--", "-c", "x"]);
+        let options = parse(&["--", "--which"]).unwrap();
+        assert_eq!(options.command, None);
+        assert_eq!(passed_args(&options), ["--", "--which"]);
+    }
+    
+    #[test]
+    fn rejects_invalid_options() {
+        let error = |args: &[&str]| parse(args).unwrap_err().to_string();
+        assert!(matches!(parse(&["--pyenv-python-foo"]), Err(WrapperOptionsError::Unknown { .. })));
+        assert!(matches!(parse(&["--which", "--path"]), Err(WrapperOptionsError::Conflict { .. })));
+        assert!(matches!(parse(&["--prompt"]), Err(WrapperOptionsError::MissingValue { .. })));
+        assert!(matches!(parse(&["--path=json"]), Err(WrapperOptionsError::UnexpectedValue { .. })));
+        assert!(matches!(parse(&["--explain=yes"]), Err(WrapperOptionsError::UnexpectedValue { .. })));
+        assert!(matches!(parse(&["--json", "--path"]), Err(WrapperOptionsError::JsonUnsupported)));
+        assert!(matches!(parse(&["--json"]), Err(WrapperOptionsError::JsonUnsupported)));
+        assert!(error(&["--pyenv-python-foo"]).contains("--pyenv-python-foo"));
+    }
+}
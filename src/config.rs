@@ -0,0 +1,21 @@
+use std::env;
+
+/// Reads the setting `$PYENV_PYTHON_{name}`, trimmed and lowercased,
+/// or [`None`] if it's unset or not UTF-8.
+pub fn value(name: &str) -> Option<String> {
+    let value = env::var(format!("PYENV_PYTHON_{}", name)).ok()?;
+    Some(value.trim().to_ascii_lowercase())
+}
+
+/// Reads the boolean setting `$PYENV_PYTHON_{name}`.
+///
+/// `1`, `true`, `yes`, and `on` are `true`, and `0`, `false`, `no`, `off`, and empty are `false`,
+/// all case-insensitively.
+/// If it's unset or anything else, `default` is returned.
+pub fn flag(name: &str, default: bool) -> bool {
+    match value(name).as_deref() {
+        Some("1" | "true" | "yes" | "on") => true,
+        Some("0" | "false" | "no" | "off" | "") => false,
+        _ => default,
+    }
+}
@@ -0,0 +1,300 @@
+use std::{fmt, fs, io};
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+
+use thiserror::Error;
+
+/// A `.env`-style file of `KEY=value` lines, like `.pyenv-python.env`.
+///
+/// Blank lines and `#` comments are ignored, and lines may start with `export`.
+/// Values may be unquoted (ending at a ` #` comment), single-quoted (literal),
+/// or double-quoted (with `\` escapes like `\n`).
+///
+/// Unquoted and double-quoted values expand `$VAR`, `${VAR}`, and `${VAR:-default}`,
+/// using the variables earlier in the file or else a given lookup (usually the environment).
+/// Undefined variables expand to nothing, and `\$` is a literal `$`.
+#[derive(Debug)]
+pub struct EnvFile {
+    path: PathBuf,
+    vars: Vec<(String, String)>,
+}
+
+impl Display for EnvFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())
+    }
+}
+
+/// Why an [`EnvFile`] could not be read.
+#[derive(Debug, Error)]
+pub enum EnvFileError {
+    #[error("could not read env file {path}")]
+    IOError { path: PathBuf, source: io::Error },
+    #[error("invalid env file {path} at line {line}: {message}")]
+    Invalid { path: PathBuf, line: usize, message: &'static str },
+}
+
+impl EnvFile {
+    /// Reads and parses the env file at `path`, or returns [`None`] if it doesn't exist.
+    /// Variables not set earlier in the file are expanded using `lookup`.
+    ///
+    /// See [`EnvFileError`] for possible errors.
+    pub fn read(
+        path: PathBuf,
+        lookup: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Option<Self>, EnvFileError> {
+        use EnvFileError::*;
+        let contents = match fs::read_to_string(path.as_path()) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(IOError { path, source }),
+        };
+        let mut vars = Vec::<(String, String)>::new();
+        for (i, line) in contents.lines().enumerate() {
+            let lookup = |name: &str| vars
+                .iter()
+                .rev()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| lookup(name));
+            match parse_line(line, &lookup) {
+                Ok(None) => {}
+                Ok(Some(var)) => vars.push(var),
+                Err(message) => return Err(Invalid {
+                    path,
+                    line: i + 1,
+                    message,
+                }),
+            }
+        }
+        Ok(Some(Self { path, vars }))
+    }
+    
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    
+    /// The variables in the order they're set.
+    pub fn vars(&self) -> &[(String, String)] {
+        self.vars.as_slice()
+    }
+}
+
+type Lookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn parse_line(line: &str, lookup: Lookup) -> Result<Option<(String, String)>, &'static str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let line = line
+        .strip_prefix("export")
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .unwrap_or(line)
+        .trim_start();
+    let equals = line.find('=').ok_or("expected KEY=value")?;
+    let (key, value) = line.split_at(equals);
+    let key = key.trim_end();
+    let is_valid_key = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_valid_key {
+        return Err("invalid variable name");
+    }
+    let value = parse_value(value[1..].trim_start(), lookup)?;
+    Ok(Some((key.to_string(), value)))
+}
+
+fn parse_value(value: &str, lookup: Lookup) -> Result<String, &'static str> {
+    let mut chars = value.chars().peekable();
+    let mut parsed = String::new();
+    match chars.peek() {
+        Some('\'') => {
+            let value = &value[1..];
+            let end = value.find('\'').ok_or("unterminated single quote")?;
+            let (quoted, rest) = value.split_at(end);
+            check_trailing(&rest[1..])?;
+            parsed.push_str(quoted);
+        }
+        Some('"') => {
+            chars.next();
+            loop {
+                match chars.next().ok_or("unterminated double quote")? {
+                    '"' => break,
+                    '\\' => parsed.push(match chars.next().ok_or("unterminated double quote")? {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        c => c,
+                    }),
+                    '$' => expand_var(&mut chars, lookup, &mut parsed)?,
+                    c => parsed.push(c),
+                }
+            }
+            check_trailing(chars.collect::<String>().as_str())?;
+        }
+        _ => {
+            let end = value.find(" #").unwrap_or(value.len());
+            let mut chars = value[..end].trim_end().chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' if chars.peek() == Some(&'$') => parsed.push(chars.next().unwrap()),
+                    '$' => expand_var(&mut chars, lookup, &mut parsed)?,
+                    c => parsed.push(c),
+                }
+            }
+        }
+    };
+    Ok(parsed)
+}
+
+/// Expand a `$VAR`, `${VAR}`, or `${VAR:-default}` (after the `$`) into `expanded`.
+/// A `$` not followed by a variable name is kept as is.
+fn expand_var(
+    chars: &mut Peekable<Chars>,
+    lookup: Lookup,
+    expanded: &mut String,
+) -> Result<(), &'static str> {
+    let is_name_char = |c: &char| c.is_ascii_alphanumeric() || *c == '_';
+    if chars.peek() == Some(&'{') {
+        chars.next();
+        let mut braced = String::new();
+        loop {
+            match chars.next().ok_or("unterminated ${")? {
+                '}' => break,
+                c => braced.push(c),
+            }
+        }
+        let (name, default) = match braced.find(":-") {
+            Some(i) => (&braced[..i], Some(&braced[i + 2..])),
+            None => (braced.as_str(), None),
+        };
+        if name.is_empty() || !name.chars().all(|c| is_name_char(&c)) {
+            return Err("invalid variable name in ${}");
+        }
+        match lookup(name).filter(|value| !value.is_empty()) {
+            Some(value) => expanded.push_str(value.as_str()),
+            None => expanded.push_str(default.unwrap_or_default()),
+        }
+    } else {
+        let mut name = String::new();
+        while let Some(c) = chars.peek().copied().filter(is_name_char) {
+            name.push(c);
+            chars.next();
+        }
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            expanded.push_str(lookup(name.as_str()).unwrap_or_default().as_str());
+        }
+    }
+    Ok(())
+}
+
+/// Only a comment may follow a quoted value.
+fn check_trailing(rest: &str) -> Result<(), &'static str> {
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err("unexpected characters after quoted value")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/me".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+    
+    fn parse(line: &str) -> Result<Option<(String, String)>, &'static str> {
+        parse_line(line, &lookup)
+    }
+    
+    fn value(line: &str) -> String {
+        parse(line).unwrap().unwrap().1
+    }
+    
+    #[test]
+    fn skips_blank_and_comment_lines() {
+        assert_eq!(parse(""), Ok(None));
+        assert_eq!(parse("   "), Ok(None));
+        assert_eq!(parse("# FOO=bar"), Ok(None));
+        assert_eq!(parse("  # FOO=bar"), Ok(None));
+    }
+    
+    #[test]
+    fn parses_key_and_value() {
+        assert_eq!(parse("FOO=bar"), Ok(Some(("FOO".to_string(), "bar".to_string()))));
+        assert_eq!(parse("FOO = bar"), Ok(Some(("FOO".to_string(), "bar".to_string()))));
+        assert_eq!(parse("FOO="), Ok(Some(("FOO".to_string(), String::new()))));
+    }
+    
+    #[test]
+    fn strips_export() {
+        assert_eq!(parse("export FOO=bar"), Ok(Some(("FOO".to_string(), "bar".to_string()))));
+        assert_eq!(parse("exportFOO=bar"), Ok(Some(("exportFOO".to_string(), "bar".to_string()))));
+    }
+    
+    #[test]
+    fn unquoted_values_end_at_comments() {
+        assert_eq!(value("FOO=bar # comment"), "bar");
+        assert_eq!(value("FOO=bar#baz"), "bar#baz");
+        assert_eq!(value("FOO=a b  "), "a b");
+    }
+    
+    #[test]
+    fn single_quotes_are_literal() {
+        assert_eq!(value(r"FOO='$HOME \n # not a comment'"), r"$HOME \n # not a comment");
+        assert_eq!(value("FOO='bar' # comment"), "bar");
+    }
+    
+    #[test]
+    fn double_quotes_escape_and_expand() {
+        assert_eq!(value(r#"FOO="a\nb\t\"c\"""#), "a\nb\t\"c\"");
+        assert_eq!(value(r#"FOO="$HOME/bin # not a comment""#), "/home/me/bin # not a comment");
+        assert_eq!(value(r#"FOO="\$HOME""#), "$HOME");
+        assert_eq!(value(r#"FOO="bar" # comment"#), "bar");
+    }
+    
+    #[test]
+    fn expands_vars() {
+        assert_eq!(value("FOO=$HOME/bin"), "/home/me/bin");
+        assert_eq!(value("FOO=${HOME}bin"), "/home/mebin");
+        assert_eq!(value("FOO=$UNDEFINED/bin"), "/bin");
+        assert_eq!(value(r"FOO=\$HOME"), "$HOME");
+        assert_eq!(value("FOO=cost: $"), "cost: $");
+    }
+    
+    #[test]
+    fn expands_defaults() {
+        assert_eq!(value("FOO=${HOME:-/root}"), "/home/me");
+        assert_eq!(value("FOO=${UNDEFINED:-/root}"), "/root");
+        assert_eq!(value("FOO=${EMPTY:-/root}"), "/root");
+        assert_eq!(value("FOO=${EMPTY}"), "");
+    }
+    
+    #[test]
+    fn rejects_invalid_lines() {
+        assert_eq!(parse("FOO"), Err("expected KEY=value"));
+        assert_eq!(parse("=bar"), Err("invalid variable name"));
+        assert_eq!(parse("1FOO=bar"), Err("invalid variable name"));
+        assert_eq!(parse("FOO-BAR=bar"), Err("invalid variable name"));
+        assert_eq!(parse("FOO='bar"), Err("unterminated single quote"));
+        assert_eq!(parse(r#"FOO="bar"#), Err("unterminated double quote"));
+        assert_eq!(parse(r#"FOO="bar\"#), Err("unterminated double quote"));
+        assert_eq!(parse("FOO='bar' baz"), Err("unexpected characters after quoted value"));
+        assert_eq!(parse(r#"FOO="bar"baz"#), Err("unexpected characters after quoted value"));
+        assert_eq!(parse("FOO=${HOME"), Err("unterminated ${"));
+        assert_eq!(parse("FOO=${}"), Err("invalid variable name in ${}"));
+        assert_eq!(parse("FOO=${HO-ME}"), Err("invalid variable name in ${}"));
+    }
+}
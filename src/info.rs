@@ -0,0 +1,392 @@
+use std::{fmt, fs};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::installed::compare_versions;
+use crate::{PythonPrefix, PythonPrefixError};
+
+/// A `python` implementation.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PythonImplementation {
+    CPython,
+    PyPy,
+    GraalPy,
+}
+
+impl Display for PythonImplementation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::CPython => "CPython",
+            Self::PyPy => "PyPy",
+            Self::GraalPy => "GraalPy",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Metadata about a `python` installation,
+/// determined from its install layout rather than by running it.
+///
+/// The exact version comes from `include/pythonX.Y*/patchlevel.h`,
+/// which may not be installed (e.g. without a `python3-dev` package),
+/// in which case just `X.Y` comes from `lib/pythonX.Y*` or a venv's `pyvenv.cfg`.
+/// ABI flags, like `d` (debug) and `t` (free-threaded), come from those directory names.
+#[derive(Debug)]
+pub struct PythonInfo {
+    implementation: PythonImplementation,
+    version: String,
+    exact: bool,
+    abi_flags: String,
+    prefix: PathBuf,
+    base_prefix: PathBuf,
+}
+
+impl Display for PythonInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.implementation, self.version)?;
+        let flags = [
+            (self.is_debug(), "debug"),
+            (self.is_free_threaded(), "free-threaded"),
+        ]
+            .iter()
+            .filter(|(is_set, _)| *is_set)
+            .map(|(_, flag)| *flag)
+            .collect::<Vec<_>>();
+        if !flags.is_empty() {
+            write!(f, " ({})", flags.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Why [`PythonInfo`] couldn't be determined.
+#[derive(Debug, Error)]
+pub enum PythonInfoError {
+    #[error("couldn't find the python prefix")]
+    Prefix(#[from] PythonPrefixError),
+    #[error("couldn't determine the python version from the install layout at {prefix}")]
+    UnknownLayout { prefix: PathBuf },
+}
+
+/// What's found in a (non-venv) prefix's layout.
+struct Layout {
+    implementation: PythonImplementation,
+    version: String,
+    exact: bool,
+    abi_flags: String,
+}
+
+impl PythonInfo {
+    /// Determines the info for the `python` executable at `python_path`
+    /// from the install layout of its prefix (see [`PythonPrefix`]).
+    /// For a venv, that's its base prefix, or else just its `pyvenv.cfg`.
+    ///
+    /// See [`PythonInfoError`] for possible errors.
+    pub fn detect(python_path: &Path) -> Result<Self, PythonInfoError> {
+        let prefixes = PythonPrefix::find(python_path)?;
+        let (prefix, base_prefix) = (prefixes.prefix(), prefixes.base_prefix());
+        if let Some(layout) = Layout::detect(base_prefix, python_path) {
+            return Ok(Self::new(layout, prefix, base_prefix));
+        }
+        let unknown = || PythonInfoError::UnknownLayout {
+            prefix: base_prefix.to_path_buf(),
+        };
+        let config = prefixes.config().ok_or_else(unknown)?;
+        let version = config.version().ok_or_else(unknown)?.to_string();
+        let implementation = match config.get("implementation").map(|name| name.to_lowercase()).as_deref() {
+            Some("pypy") => PythonImplementation::PyPy,
+            Some("graalvm" | "graalpy") => PythonImplementation::GraalPy,
+            _ => PythonImplementation::CPython,
+        };
+        let layout = Layout {
+            implementation,
+            version,
+            exact: false,
+            abi_flags: String::new(),
+        };
+        Ok(Self::new(layout, prefix, base_prefix))
+    }
+    
+    fn new(layout: Layout, prefix: &Path, base_prefix: &Path) -> Self {
+        let Layout {
+            implementation,
+            version,
+            exact,
+            abi_flags,
+        } = layout;
+        Self {
+            implementation,
+            version,
+            exact,
+            abi_flags,
+            prefix: prefix.to_path_buf(),
+            base_prefix: base_prefix.to_path_buf(),
+        }
+    }
+    
+    pub fn implementation(&self) -> PythonImplementation {
+        self.implementation
+    }
+    
+    /// The `python` language version (not the PyPy or GraalPy version),
+    /// like `3.12.1` or `3.13.0a1+`, or just `3.12` if it's not [`PythonInfo::is_exact`].
+    pub fn version(&self) -> &str {
+        self.version.as_str()
+    }
+    
+    /// If the [`PythonInfo::version`] is the exact one from `patchlevel.h`.
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+    
+    /// The ABI flags, like `sys.abiflags`.
+    pub fn abi_flags(&self) -> &str {
+        self.abi_flags.as_str()
+    }
+    
+    pub fn is_debug(&self) -> bool {
+        self.abi_flags.contains('d')
+    }
+    
+    pub fn is_free_threaded(&self) -> bool {
+        self.abi_flags.contains('t')
+    }
+    
+    /// Like `sys.prefix`.
+    pub fn prefix(&self) -> &Path {
+        self.prefix.as_path()
+    }
+    
+    /// Like `sys.base_prefix`, which is different from the [`PythonInfo::prefix`] in a venv.
+    pub fn base_prefix(&self) -> &Path {
+        self.base_prefix.as_path()
+    }
+    
+    pub fn is_venv(&self) -> bool {
+        self.prefix != self.base_prefix
+    }
+}
+
+impl Layout {
+    /// Detects the layout of `prefix` for the `python` executable at `python_path`.
+    fn detect(prefix: &Path, python_path: &Path) -> Option<Self> {
+        let version = python_version(prefix, python_path);
+        Self::from_include_dir(prefix, version.as_deref())
+            .or_else(|| Self::from_lib_dir(prefix, version.as_deref()))
+    }
+    
+    /// From `include/pythonX.Y*/patchlevel.h` (or `include/pypyX.Y/`),
+    /// for the `X.Y` `version` of the executable if it's known.
+    ///
+    /// Several `-dev` packages may be installed in the same prefix (like `/usr`),
+    /// so the version is only exact if exactly one `patchlevel.h` could belong to the executable.
+    fn from_include_dir(prefix: &Path, version: Option<&str>) -> Option<Self> {
+        let dirs = versioned_dirs(prefix.join("include").as_path())
+            .into_iter()
+            .filter(|(dir, (dir_version, _))| {
+                is_version(dir_version, version) && dir.join("patchlevel.h").is_file()
+            })
+            .collect::<Vec<_>>();
+        let exact = dirs.len() == 1;
+        let (dir, (_, abi_flags)) = dirs.into_iter().next()?;
+        let patchlevel = fs::read_to_string(dir.join("patchlevel.h")).ok()?;
+        let define = |name: &str| patchlevel
+            .lines()
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                match (words.next(), words.next(), words.next()) {
+                    (Some("#define"), Some(key), Some(value)) if key == name => Some(value.trim_matches('"')),
+                    _ => None,
+                }
+            })
+            .next()
+            .map(|value| value.to_string());
+        let version = define("PY_VERSION")?;
+        let implementation = if define("PYPY_VERSION").is_some() {
+            PythonImplementation::PyPy
+        } else {
+            implementation_of(prefix)
+        };
+        Some(Self {
+            implementation,
+            version,
+            exact,
+            abi_flags,
+        })
+    }
+    
+    /// From `lib/pythonX.Y*/` (or `lib/pypyX.Y/`), which only has the major and minor version,
+    /// for the `X.Y` `version` of the executable if it's known.
+    fn from_lib_dir(prefix: &Path, version: Option<&str>) -> Option<Self> {
+        let (_, (version, abi_flags)) = versioned_dirs(prefix.join("lib").as_path())
+            .into_iter()
+            .find(|(_, (dir_version, _))| is_version(dir_version, version))?;
+        Some(Self {
+            implementation: implementation_of(prefix),
+            version,
+            exact: false,
+            abi_flags,
+        })
+    }
+}
+
+/// The `X.Y` version of the `python` executable at `python_path` in `prefix`, if it can be told from the layout:
+/// the `pythonX.Y` name it resolves to (like `python3 -> python3.11`),
+/// or else the `lib/pythonX.Y` with an `os.py` (like `python` itself looks for),
+/// or else the only `lib/pythonX.Y`.
+fn python_version(prefix: &Path, python_path: &Path) -> Option<String> {
+    let real_name = fs::canonicalize(python_path)
+        .ok()
+        .and_then(|path| path.file_name()?.to_str().map(|name| name.to_string()));
+    if let Some((version, _)) = real_name.as_deref().and_then(parse_versioned_name) {
+        return Some(version);
+    }
+    let lib_dirs = versioned_dirs(prefix.join("lib").as_path());
+    let mut landmarks = lib_dirs
+        .iter()
+        .filter(|(dir, _)| dir.join("os.py").is_file())
+        .map(|(_, (version, _))| version);
+    match (landmarks.next(), landmarks.next()) {
+        (Some(version), None) => return Some(version.clone()),
+        (Some(_), Some(_)) => return None,
+        (None, _) => {}
+    }
+    match lib_dirs.as_slice() {
+        [(_, (version, _))] => Some(version.clone()),
+        _ => None,
+    }
+}
+
+/// If `dir_version` is the executable's `version`, or could be if it's not known.
+pub(crate) fn is_version(dir_version: &str, version: Option<&str>) -> bool {
+    match version {
+        Some(version) => dir_version == version,
+        None => true,
+    }
+}
+
+/// Parses a `pythonX.Y*` or `pypyX.Y*` name into its `X.Y` version and the ABI flags after it.
+pub(crate) fn parse_versioned_name(name: &str) -> Option<(String, String)> {
+    let rest = name.strip_prefix("python").or_else(|| name.strip_prefix("pypy"))?;
+    let flags_start = rest
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(rest.len());
+    let (version, flags) = rest.split_at(flags_start);
+    if !version.contains('.') || !flags.chars().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+    Some((version.to_string(), flags.to_string()))
+}
+
+/// The `pythonX.Y*` and `pypyX.Y*` directories in `dir`,
+/// with their `X.Y` version and the ABI flags after it, newest first.
+fn versioned_dirs(dir: &Path) -> Vec<(PathBuf, (String, String))> {
+    let mut dirs = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            Some((entry.path(), parse_versioned_name(name.as_str())?))
+        })
+        .collect::<Vec<_>>();
+    // without ABI flags first for the same version, since that's the usual build
+    dirs.sort_by(|(_, (a, a_flags)), (_, (b, b_flags))| compare_versions(b, a).then_with(|| a_flags.cmp(b_flags)));
+    dirs
+}
+
+/// Guess the implementation from the distinctive directories of PyPy and GraalPy.
+fn implementation_of(prefix: &Path) -> PythonImplementation {
+    let lib_has = |name_prefix: &str| fs::read_dir(prefix.join("lib"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .any(|entry| entry.file_name().to_string_lossy().starts_with(name_prefix));
+    if prefix.join("lib_pypy").is_dir() || lib_has("pypy") {
+        PythonImplementation::PyPy
+    } else if prefix.join("lib-graalpython").is_dir() || lib_has("graalpy") {
+        PythonImplementation::GraalPy
+    } else {
+        PythonImplementation::CPython
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::temp_prefix::{TempPrefix, PATCHLEVEL_3_11, PATCHLEVEL_3_12};
+    
+    use super::*;
+    
+    fn layout(prefix: &TempPrefix) -> Option<Layout> {
+        Layout::detect(prefix.path(), prefix.path().join("bin/python").as_path())
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn uses_patchlevel_of_resolved_executable_version() {
+        let prefix = TempPrefix::new("resolved", &[
+            ("bin/python3.11", ""),
+            ("bin/python", "-> python3.11"),
+            ("include/python3.11/patchlevel.h", PATCHLEVEL_3_11),
+            ("include/python3.12/patchlevel.h", PATCHLEVEL_3_12),
+        ]);
+        let layout = layout(&prefix).unwrap();
+        assert_eq!(layout.version, "3.11.4");
+        assert!(layout.exact);
+    }
+    
+    #[test]
+    fn uses_patchlevel_of_os_py_landmark_version() {
+        let prefix = TempPrefix::new("landmark", &[
+            ("bin/python", ""),
+            ("lib/python3.11/os.py", ""),
+            ("lib/python3.12/site-packages/foo.py", ""),
+            ("include/python3.11/patchlevel.h", PATCHLEVEL_3_11),
+            ("include/python3.12/patchlevel.h", PATCHLEVEL_3_12),
+        ]);
+        let layout = layout(&prefix).unwrap();
+        assert_eq!(layout.version, "3.11.4");
+        assert!(layout.exact);
+    }
+    
+    #[test]
+    fn is_inexact_if_several_patchlevels_match() {
+        let prefix = TempPrefix::new("several", &[
+            ("bin/python", ""),
+            ("lib/python3.11/os.py", ""),
+            ("include/python3.11/patchlevel.h", PATCHLEVEL_3_11),
+            ("include/python3.11d/patchlevel.h", PATCHLEVEL_3_11),
+        ]);
+        let layout = layout(&prefix).unwrap();
+        assert_eq!(layout.version, "3.11.4");
+        assert_eq!(layout.abi_flags, "");
+        assert!(!layout.exact);
+    }
+    
+    #[test]
+    fn is_inexact_if_executable_version_is_unknown() {
+        let prefix = TempPrefix::new("unknown", &[
+            ("bin/python", ""),
+            ("lib/python3.11/site-packages/foo.py", ""),
+            ("lib/python3.12/site-packages/foo.py", ""),
+            ("include/python3.11/patchlevel.h", PATCHLEVEL_3_11),
+            ("include/python3.12/patchlevel.h", PATCHLEVEL_3_12),
+        ]);
+        assert!(!layout(&prefix).unwrap().exact);
+    }
+    
+    #[test]
+    fn falls_back_to_lib_dir_without_patchlevel() {
+        let prefix = TempPrefix::new("lib", &[
+            ("bin/python", ""),
+            ("lib/python3.11t/os.py", ""),
+            ("include/python3.12/patchlevel.h", PATCHLEVEL_3_12),
+        ]);
+        let layout = layout(&prefix).unwrap();
+        assert_eq!(layout.version, "3.11");
+        assert_eq!(layout.abi_flags, "t");
+        assert!(!layout.exact);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{fmt, fs, io};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::{PyenvPythonExecutableError, PyenvRoot, PythonExecutable, Virtualenv};
+
+/// What kind of `python` environment an [`InstalledVersion`] is.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum InstalledVersionKind {
+    /// A normal `python` installation, like one from `pyenv install`.
+    Python,
+    /// A virtualenv, like one from `pyenv virtualenv`.
+    Virtualenv,
+    /// A `conda` environment, like one from `pyenv install miniconda3-latest`.
+    Conda,
+}
+
+impl Display for InstalledVersionKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Python => "python",
+            Self::Virtualenv => "virtualenv",
+            Self::Conda => "conda",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A version installed in `$PYENV_ROOT/versions`.
+#[derive(Debug)]
+pub struct InstalledVersion {
+    name: String,
+    path: PathBuf,
+    kind: InstalledVersionKind,
+    alias_of: Option<PathBuf>,
+    broken: Option<PyenvPythonExecutableError>,
+}
+
+impl Display for InstalledVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl InstalledVersion {
+    fn new(name: String, path: PathBuf) -> Self {
+        let kind = if path.join("conda-meta").is_dir() {
+            InstalledVersionKind::Conda
+        } else if Virtualenv::detect(path.as_path()).is_some() {
+            InstalledVersionKind::Virtualenv
+        } else {
+            InstalledVersionKind::Python
+        };
+        let alias_of = fs::symlink_metadata(path.as_path())
+            .ok()
+            .filter(|metadata| metadata.file_type().is_symlink())
+            .and_then(|_| fs::read_link(path.as_path()).ok());
+        let broken = PythonExecutable::new(path.join("bin").join("python"))
+            .err()
+            .map(|(error, _)| error);
+        Self {
+            name,
+            path,
+            kind,
+            alias_of,
+            broken,
+        }
+    }
+    
+    /// The version name, like `3.9.6`, a virtualenv name, or `3.9.6/envs/name`
+    /// for a `pyenv-virtualenv` virtualenv inside a version.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+    
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    
+    pub fn kind(&self) -> InstalledVersionKind {
+        self.kind
+    }
+    
+    /// What this version is a symlink to, if it's an alias like `3.9 -> 3.9.6`.
+    pub fn alias_of(&self) -> Option<&Path> {
+        self.alias_of.as_deref()
+    }
+    
+    /// Why this version's `bin/python` can't be run, if it's broken.
+    pub fn broken(&self) -> Option<&PyenvPythonExecutableError> {
+        self.broken.as_ref()
+    }
+}
+
+/// A part of a version name for sorting numerically,
+/// so `3.10` is after `3.9`, and numbers are before names.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+enum VersionPart<'a> {
+    Number(u64),
+    Name(&'a str),
+}
+
+fn version_parts(name: &str) -> Vec<VersionPart<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = name;
+    while let Some(c) = rest.chars().next() {
+        let is_digit = c.is_ascii_digit();
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != is_digit)
+            .unwrap_or(rest.len());
+        let (part, next) = rest.split_at(end);
+        parts.push(match part.parse() {
+            Ok(number) if is_digit => VersionPart::Number(number),
+            _ => VersionPart::Name(part),
+        });
+        rest = next;
+    }
+    parts
+}
+
+/// Compares version names numerically, like `pyenv versions` sorts them.
+pub(crate) fn compare_versions(a: &str, b: &str) -> Ordering {
+    version_parts(a).cmp(&version_parts(b))
+}
+
+/// Lists the installed versions in `versions_dir`, including `pyenv-virtualenv`'s `envs`, sorted numerically.
+fn installed_versions(versions_dir: &Path) -> io::Result<Vec<InstalledVersion>> {
+    let mut versions = Vec::new();
+    for entry in fs::read_dir(versions_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = entry.path();
+        let is_alias = entry.file_type()?.is_symlink();
+        if !path.is_dir() && !is_alias {
+            continue;
+        }
+        if !is_alias {
+            if let Ok(envs) = fs::read_dir(path.join("envs")) {
+                for env in envs.flatten() {
+                    let env_name = format!("{}/envs/{}", name, env.file_name().to_string_lossy());
+                    versions.push(InstalledVersion::new(env_name, env.path()));
+                }
+            }
+        }
+        versions.push(InstalledVersion::new(name, path));
+    }
+    versions.sort_by(|a, b| compare_versions(a.name(), b.name()));
+    Ok(versions)
+}
+
+impl PyenvRoot {
+    /// The versions installed in `$PYENV_ROOT/versions`, sorted numerically,
+    /// like `pyenv versions` lists them (without `system`).
+    pub fn versions(&self) -> io::Result<Vec<InstalledVersion>> {
+        installed_versions(self.path().join("versions").as_path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn sorted(names: &[&'static str]) -> Vec<&'static str> {
+        let mut names = names.to_vec();
+        names.sort_by(|a, b| compare_versions(a, b));
+        names
+    }
+    
+    #[test]
+    fn compares_numbers_numerically() {
+        assert_eq!(compare_versions("3.10.0", "3.9.6"), Ordering::Greater);
+        assert_eq!(compare_versions("3.9.10", "3.9.9"), Ordering::Greater);
+        assert_eq!(compare_versions("3.9.6", "3.9.6"), Ordering::Equal);
+        assert_eq!(compare_versions("3.9", "3.9.0"), Ordering::Less);
+    }
+    
+    #[test]
+    fn sorts_numbers_before_names() {
+        assert_eq!(
+            sorted(&["pypy3.9-7.3.9", "3.10.0", "miniconda3-latest", "3.9.6", "3.10.0a1", "3.9.6/envs/proj"]),
+            ["3.9.6", "3.9.6/envs/proj", "3.10.0", "3.10.0a1", "miniconda3-latest", "pypy3.9-7.3.9"],
+        );
+    }
+    
+    #[test]
+    fn splits_digit_and_name_runs() {
+        use VersionPart::*;
+        assert_eq!(version_parts("3.10.0a1"), [Number(3), Name("."), Number(10), Name("."), Number(0), Name("a"), Number(1)]);
+        assert_eq!(version_parts(""), []);
+    }
+    
+    #[test]
+    fn keeps_huge_numbers_as_names() {
+        use VersionPart::*;
+        assert_eq!(version_parts("99999999999999999999"), [Name("99999999999999999999")]);
+    }
+}
@@ -11,7 +11,28 @@ use is_executable::IsExecutable;
 use same_file::Handle;
 use thiserror::Error;
 
+pub use env_file::{EnvFile, EnvFileError};
+pub use info::{PythonImplementation, PythonInfo, PythonInfoError};
+pub use installed::{InstalledVersion, InstalledVersionKind};
+pub use pipx::{PipxApp, PipxHome, PipxHomeError};
+pub use prefix::{PythonPrefix, PythonPrefixError};
+pub use prompt::{PromptTemplate, PromptTemplateError};
+pub use shims::ShimDirs;
+pub use virtualenv::{PyvenvCfg, Virtualenv};
+
+pub mod config;
+mod env_file;
+mod info;
+mod installed;
+mod pipx;
+mod prefix;
+mod prompt;
+mod shims;
+#[cfg(test)]
+mod temp_prefix;
+pub mod trace;
 mod version;
+mod virtualenv;
 
 /// A root `pyenv` directory.
 #[derive(Debug)]
@@ -90,6 +111,43 @@ impl Display for PyenvVersionFrom {
 pub struct PyenvVersion {
     version: String,
     from: PyenvVersionFrom,
+    /// The `.python-version` or global `version` file the version was read from,
+    /// or [`None`] if it's from the shell.
+    file: Option<PathBuf>,
+}
+
+impl PyenvVersion {
+    /// The version name, like `3.9.6` or a virtualenv name.
+    pub fn name(&self) -> &str {
+        self.version.as_str()
+    }
+    
+    pub fn origin(&self) -> PyenvVersionFrom {
+        self.from
+    }
+    
+    /// The file the version was read from, or [`None`] if it's from the shell (`$PYENV_VERSION`).
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+    
+    /// What set the version, as `pyenv version-origin` prints it,
+    /// i.e. the version file or else the `PYENV_VERSION environment variable`.
+    pub fn set_by(&self) -> String {
+        match self.file() {
+            Some(file) => file.display().to_string(),
+            None => "PYENV_VERSION environment variable".to_string(),
+        }
+    }
+    
+    /// The project directory, i.e. where the local `.python-version` was found,
+    /// or [`None`] if the version isn't local.
+    pub fn project_dir(&self) -> Option<&Path> {
+        match self.from {
+            PyenvVersionFrom::Local => self.file()?.parent(),
+            _ => None,
+        }
+    }
 }
 
 impl Display for PyenvVersion {
@@ -99,14 +157,24 @@ impl Display for PyenvVersion {
 }
 
 impl PyenvRoot {
+    pub fn path(&self) -> &Path {
+        self.root.as_path()
+    }
+    
     /// Returns the current pyenv version as determined by
-    /// <https://github.com/pyenv/pyenv#choosing-the-python-version>.
-    fn version(&self) -> Result<PyenvVersion, ()> {
+    /// <https://github.com/pyenv/pyenv#choosing-the-python-version>,
+    /// or [`None`] if no version is set.
+    pub fn version(&self) -> Option<PyenvVersion> {
         self
             .root
             .as_path()
             .apply(version::pyenv_version)
-            .ok_or(())
+    }
+    
+    /// Returns the version file that's used if `$PYENV_VERSION` isn't set, like `pyenv version-file`,
+    /// i.e. the nearest local `.python-version` or else the global `$PYENV_ROOT/version`.
+    pub fn version_file(&self) -> PathBuf {
+        version::pyenv_version_file(self.root.as_path())
     }
     
     fn python_path(&self, path_components: &[&str]) -> UncheckedPythonPath {
@@ -118,6 +186,11 @@ impl PyenvRoot {
         UncheckedPythonPath::from_existing(path)
     }
     
+    /// The directory of an installed `pyenv` version, i.e. `$PYENV_ROOT/versions/$version`.
+    pub fn version_dir(&self, version: &PyenvVersion) -> PathBuf {
+        self.root.join("versions").join(version.version.as_str())
+    }
+    
     fn python_version_path(&self, version: &PyenvVersion) -> UncheckedPythonPath {
         self.python_path(&[
             "versions",
@@ -278,6 +351,7 @@ pub struct Pyenv {
     root: PyenvRoot,
     version: PyenvVersion,
     python_path: PythonExecutable,
+    virtualenv: Option<Virtualenv>,
 }
 
 impl Display for Pyenv {
@@ -286,6 +360,27 @@ impl Display for Pyenv {
     }
 }
 
+impl Pyenv {
+    pub fn root(&self) -> &PyenvRoot {
+        &self.root
+    }
+    
+    pub fn version(&self) -> &PyenvVersion {
+        &self.version
+    }
+    
+    /// The versions installed in this `pyenv` root (see [`PyenvRoot::versions`]).
+    pub fn versions(&self) -> io::Result<Vec<InstalledVersion>> {
+        self.root.versions()
+    }
+    
+    /// The virtualenv this version is, if it's one,
+    /// like one created by `pyenv-virtualenv`.
+    pub fn virtualenv(&self) -> Option<&Virtualenv> {
+        self.virtualenv.as_ref()
+    }
+}
+
 impl HasPython for Pyenv {
     fn python(&self) -> &PythonExecutable {
         &self.python_path
@@ -317,11 +412,23 @@ pub enum PyenvError {
     NoExecutable {
         #[source] error: PyenvPythonExecutableError,
         root: PyenvRoot,
-        version: PyenvVersion,
+        /// Boxed to keep [`PyenvError`] and [`PythonError`] small.
+        version: Box<PyenvVersion>,
         python_path: PathBuf,
     },
 }
 
+impl PyenvError {
+    /// The `pyenv` root, if it was found.
+    pub fn root(&self) -> Option<&PyenvRoot> {
+        match self {
+            Self::NoRoot { .. } => None,
+            Self::NoVersion { root } => Some(root),
+            Self::NoExecutable { root, .. } => Some(root),
+        }
+    }
+}
+
 impl Pyenv {
     /// Looks up the current `pyenv` `python` executable and version,
     /// or returns which part could not be found.
@@ -329,25 +436,42 @@ impl Pyenv {
     /// See [`PyenvError`] for possible errors.
     pub fn new() -> Result<Self, PyenvError> {
         use PyenvError::*;
-        let root = PyenvRoot::new()?;
+        let root = PyenvRoot::new().map_err(|error| {
+            crate::trace!("no pyenv root: {}", error);
+            error
+        })?;
+        crate::trace!("using pyenv root {}", root);
         // Have to use `match` here instead of `map_err()?` so rustc can see the moves are disjoint.
         let version = match root.version() {
-            Err(()) => return Err(NoVersion { root }),
-            Ok(version) => version,
+            None => {
+                crate::trace!("no pyenv version found in shell, local, or global");
+                return Err(NoVersion { root });
+            }
+            Some(version) => version,
         };
+        crate::trace!("using pyenv version {} from {} (set by {})", version.name(), version.origin(), version.set_by());
         let python_path = match root.python_version_path(&version).check() {
-            Err((error, python_path)) => return Err(NoExecutable {
-                error,
-                root,
-                version,
-                python_path,
-            }),
+            Err((error, python_path)) => {
+                crate::trace!("rejected pyenv python {}: {}", python_path.display(), error);
+                return Err(NoExecutable {
+                    error,
+                    root,
+                    version: Box::new(version),
+                    python_path,
+                });
+            }
             Ok(path) => path,
         };
+        crate::trace!("found pyenv python {}", python_path);
+        let virtualenv = Virtualenv::detect(root.version_dir(&version).as_path());
+        if let Some(virtualenv) = &virtualenv {
+            crate::trace!("pyenv version is a {}", virtualenv);
+        }
         Ok(Self {
             root,
             version,
             python_path,
+            virtualenv,
         })
     }
 }
@@ -357,7 +481,15 @@ impl Pyenv {
 #[derive(Debug)]
 pub enum Python {
     Pyenv(Pyenv),
+    /// The system `python`, because the `pyenv` version is `system`.
     System(PythonExecutable),
+    /// The system `python`, because the `pyenv` `python` couldn't be found for the given `reason`.
+    ///
+    /// See [`FallbackPolicy`] for when this is allowed.
+    Fallback {
+        python: PythonExecutable,
+        reason: PyenvError,
+    },
 }
 
 impl Display for Python {
@@ -367,6 +499,8 @@ impl Display for Python {
                 write!(f, "{}", pyenv),
             Self::System(python_executable) =>
                 write!(f, "system python on $PATH at {}", python_executable),
+            Self::Fallback { python, reason } =>
+                write!(f, "system python on $PATH at {} (as a fallback because {})", python, reason),
         }
     }
 }
@@ -376,6 +510,7 @@ impl HasPython for Python {
         match self {
             Self::Pyenv(pyenv) => pyenv.python(),
             Self::System(python) => python.python(),
+            Self::Fallback { python, .. } => python.python(),
         }
     }
     
@@ -383,6 +518,7 @@ impl HasPython for Python {
         match self {
             Self::Pyenv(pyenv) => pyenv.into_python(),
             Self::System(python) => python.into_python(),
+            Self::Fallback { python, .. } => python.into_python(),
         }
     }
 }
@@ -399,58 +535,159 @@ pub enum SystemPythonError {
 
 impl Python {
     /// Lookup the current system `python`, i.e., whatever next is in `$PATH`
-    /// that's not the current executable or a `pyenv` shim.
+    /// that's not the current executable or a shim.
     ///
     /// Pass a [`PyenvRoot`] to avoid `pyenv` shims.
     /// If there is no `pyenv` root than [`None`] will work.
     ///
     /// Specifically, this returns the next `python` on `$PATH`,
-    /// excluding the current executable and `$PYENV_ROOT/shims/python`.
+    /// excluding the current executable (or a copy of it), `$PYENV_ROOT/shims/python`,
+    /// and the shim directories of `asdf`, `mise`, and other `pyenv` roots (see [`ShimDirs`]).
     /// Otherwise, an infinite loop would be formed between ourselves and `$PYENV_ROOT/shims/python`.
     ///
     /// See [`SystemPythonError`] for possible errors.
-    pub fn system(pyenv_root: Option<PyenvRoot>) -> Result<PythonExecutable, SystemPythonError> {
+    pub fn system(pyenv_root: Option<&PyenvRoot>) -> Result<PythonExecutable, SystemPythonError> {
         use SystemPythonError::*;
         let current_python = PythonExecutable::current()?;
         let pyenv_shim_python = pyenv_root
             .map(|root| root.python_shim_path())
             .and_then(|path| path.check().ok());
+        let shim_dirs = ShimDirs::new(pyenv_root);
         let path_var = env::var_os("PATH").ok_or(NoPath)?;
-        env::split_paths(&path_var)
-            .map(|mut path| {
-                path.push(current_python.name());
-                path
-            })
-            .map(UncheckedPythonPath::from_existing)
-            .filter_map(|python| python.check().ok())
-            .find(|python| python != &current_python && Some(python) != pyenv_shim_python.as_ref())
-            .ok_or(NotInPath)
+        for dir in env::split_paths(&path_var) {
+            if shim_dirs.contains(dir.as_path()) {
+                crate::trace!("rejected $PATH entry {}: a shim directory", dir.display());
+                continue;
+            }
+            let python = match UncheckedPythonPath::from_existing(dir.join(current_python.name())).check() {
+                Ok(python) => python,
+                Err((error, path)) => {
+                    crate::trace!("rejected $PATH entry {}: {}", path.display(), error);
+                    continue;
+                }
+            };
+            let rejection = if python == current_python {
+                "this python itself"
+            } else if Some(&python) == pyenv_shim_python.as_ref() {
+                "the pyenv shim"
+            } else if shims::same_contents(python.path(), current_python.path()) {
+                "a copy of this python"
+            } else {
+                crate::trace!("found system python {}", python);
+                return Ok(python);
+            };
+            crate::trace!("rejected $PATH entry {}: {}", python, rejection);
+        }
+        Err(NotInPath)
+    }
+}
+
+/// When to fall back to the system `python` if the `pyenv` `python` can't be found,
+/// set by `$PYENV_PYTHON_FALLBACK`.
+///
+/// A `pyenv` version of `system` always uses the system `python`, since that's not a fallback.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum FallbackPolicy {
+    /// Always fall back (`always`),
+    /// even if the configured version isn't installed.
+    Always,
+    /// Only fall back if no version is configured (`unset`, the default),
+    /// i.e. if there's no `pyenv` root or no version in the shell, local, or global files.
+    #[default]
+    Unset,
+    /// Never fall back (`never`).
+    Never,
+}
+
+impl Display for FallbackPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Always => "always",
+            Self::Unset => "unset",
+            Self::Never => "never",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FallbackPolicy {
+    /// Reads the policy from `$PYENV_PYTHON_FALLBACK`,
+    /// using the [`Default`] if it's unset or invalid.
+    pub fn from_env() -> Self {
+        match config::value("FALLBACK").as_deref() {
+            Some("always") => Self::Always,
+            Some("unset") => Self::Unset,
+            Some("never") => Self::Never,
+            _ => Self::default(),
+        }
+    }
+    
+    /// If falling back to the system `python` is allowed after this `pyenv` error.
+    pub fn allows(&self, error: &PyenvError) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Unset => !matches!(error, PyenvError::NoExecutable { .. }),
+            Self::Never => false,
+        }
     }
 }
 
 #[derive(Error, Debug)]
-#[error("couldn't find pyenv and system python: {pyenv}, {system}")]
-pub struct PythonError {
-    pub pyenv: PyenvError,
-    pub system: SystemPythonError,
+pub enum PythonError {
+    /// The `pyenv` `python` couldn't be found,
+    /// and the [`FallbackPolicy`] didn't allow falling back to the system `python`.
+    #[error("{pyenv} (not falling back to system python with $PYENV_PYTHON_FALLBACK={policy})")]
+    NoFallback {
+        pyenv: PyenvError,
+        policy: FallbackPolicy,
+    },
+    /// Neither the `pyenv` `python` nor the system `python` could be found.
+    #[error("couldn't find pyenv and system python: {pyenv}, {system}")]
+    NotFound {
+        pyenv: PyenvError,
+        system: SystemPythonError,
+    },
 }
 
 impl Python {
+    /// Lookup a `python` executable,
+    /// using the [`FallbackPolicy`] from the environment (see [`FallbackPolicy::from_env`]).
+    ///
+    /// See [`Python::with_fallback`].
+    pub fn new() -> Result<Self, PythonError> {
+        Self::with_fallback(FallbackPolicy::from_env())
+    }
+    
     /// Lookup a `python` executable.
     ///
     /// If a `pyenv` `python` cannot be found (see [`Pyenv::new`]),
-    /// try finding the system `python` (see [`Python::system`]).
+    /// try finding the system `python` (see [`Python::system`]) if the `policy` allows it.
+    /// If the `pyenv` version is `system`, the system `python` is always used.
     /// If neither can be found, return the errors for both in [`PythonError`].
-    pub fn new() -> Result<Self, PythonError> {
-        match Pyenv::new() {
-            Ok(pyenv) => Ok(Self::Pyenv(pyenv)),
-            Err(pyenv_error) => match Self::system(None) {
-                Ok(system_python) => Ok(Self::System(system_python)),
-                Err(system_python_error) => Err(PythonError {
-                    pyenv: pyenv_error,
-                    system: system_python_error,
-                }),
-            },
+    pub fn with_fallback(policy: FallbackPolicy) -> Result<Self, PythonError> {
+        use PythonError::*;
+        let pyenv = match Pyenv::new() {
+            Ok(pyenv) => return Ok(Self::Pyenv(pyenv)),
+            Err(pyenv) => pyenv,
+        };
+        let is_system_version = match &pyenv {
+            PyenvError::NoExecutable { version, .. } => version.name() == "system",
+            _ => false,
+        };
+        if !is_system_version && !policy.allows(&pyenv) {
+            crate::trace!("not falling back to system python with $PYENV_PYTHON_FALLBACK={}", policy);
+            return Err(NoFallback { pyenv, policy });
+        }
+        if !is_system_version {
+            crate::trace!("falling back to system python with $PYENV_PYTHON_FALLBACK={}", policy);
+        }
+        match Self::system(pyenv.root()) {
+            Ok(python) if is_system_version => Ok(Self::System(python)),
+            Ok(python) => Ok(Self::Fallback {
+                python,
+                reason: pyenv,
+            }),
+            Err(system) => Err(NotFound { pyenv, system }),
         }
     }
 }
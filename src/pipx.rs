@@ -0,0 +1,184 @@
+use std::{env, fmt, fs, io};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+/// A `pipx` home directory, i.e. what contains `venvs/<venv>/` for each installed app.
+#[derive(Debug)]
+pub struct PipxHome {
+    home: PathBuf,
+}
+
+impl Display for PipxHome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.home.display())
+    }
+}
+
+/// Why the `pipx` home could not be found.
+///
+/// See [`PipxHome::new`].
+#[derive(Debug, Error)]
+pub enum PipxHomeError {
+    /// `$PIPX_HOME` is not set and none of the default locations exist.
+    #[error("no pipx home found in $PIPX_HOME, ~/.local/pipx, or the user data dir")]
+    NotFound,
+    /// The `pipx` home is not a directory.
+    #[error("pipx home is not a directory: {home}")]
+    NotADir { home: PathBuf },
+}
+
+impl PipxHome {
+    /// Returns the `pipx` home, i.e. `$PIPX_HOME` if it is set,
+    /// or else the first existing of `~/.local/pipx` (older `pipx` versions)
+    /// and `pipx` in the user data dir (e.g. `~/.local/share/pipx`).
+    ///
+    /// See [`PipxHomeError`] for possible errors.
+    pub fn new() -> Result<Self, PipxHomeError> {
+        use PipxHomeError::*;
+        let home = match env::var_os("PIPX_HOME") {
+            Some(home) => PathBuf::from(home),
+            None => [
+                dirs_next::home_dir().map(|home| home.join(".local").join("pipx")),
+                dirs_next::data_dir().map(|data| data.join("pipx")),
+            ]
+                .iter()
+                .flatten()
+                .find(|home| home.exists())
+                .cloned()
+                .ok_or(NotFound)?,
+        };
+        if home.is_dir() {
+            Ok(Self { home })
+        } else {
+            Err(NotADir { home })
+        }
+    }
+    
+    pub fn path(&self) -> &Path {
+        self.home.as_path()
+    }
+    
+    fn venvs(&self) -> PathBuf {
+        self.home.join("venvs")
+    }
+}
+
+/// The subset of `pipx_metadata.json` needed to map app names to venvs.
+#[derive(Debug, Deserialize)]
+struct PipxMetadata {
+    main_package: PipxPackage,
+    #[serde(default)]
+    injected_packages: HashMap<String, PipxPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PipxPackage {
+    #[serde(default)]
+    apps: Vec<String>,
+    /// The apps of the package's dependencies, which are only exposed if `include_dependencies` is set.
+    #[serde(default)]
+    apps_of_dependencies: Vec<String>,
+    #[serde(default)]
+    include_dependencies: bool,
+    /// Only set for injected packages, which only expose their apps if this is set.
+    #[serde(default)]
+    include_apps: bool,
+}
+
+impl PipxPackage {
+    /// The apps `pipx` exposes for this package, including its dependencies' apps if `include_dependencies` is set.
+    fn exposed_apps(&self) -> impl Iterator<Item = &String> {
+        let apps_of_dependencies = if self.include_dependencies {
+            self.apps_of_dependencies.as_slice()
+        } else {
+            &[]
+        };
+        self.apps.iter().chain(apps_of_dependencies)
+    }
+}
+
+impl PipxMetadata {
+    fn read(venv: &Path) -> io::Result<Self> {
+        let json = fs::read(venv.join("pipx_metadata.json"))?;
+        serde_json::from_slice(json.as_slice())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+    
+    fn has_app(&self, name: &OsStr) -> bool {
+        let injected_apps = self.injected_packages
+            .values()
+            .filter(|package| package.include_apps);
+        Some(&self.main_package)
+            .into_iter()
+            .chain(injected_apps)
+            .flat_map(PipxPackage::exposed_apps)
+            .any(|app| OsStr::new(app) == name)
+    }
+}
+
+/// An app installed by `pipx` into its own venv.
+#[derive(Debug)]
+pub struct PipxApp {
+    venv: PathBuf,
+    path: PathBuf,
+}
+
+impl Display for PipxApp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "pipx venv at {}", self.venv.display())
+    }
+}
+
+impl PipxApp {
+    /// The app's venv, i.e. `$PIPX_HOME/venvs/<venv>`.
+    pub fn venv(&self) -> &Path {
+        self.venv.as_path()
+    }
+    
+    /// The app's executable in the venv's `bin` directory.
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    
+    /// The venv's `python`, which the app is meant to be run with.
+    pub fn python_path(&self) -> PathBuf {
+        venv_bin(self.venv.as_path()).join("python")
+    }
+}
+
+fn venv_bin(venv: &Path) -> PathBuf {
+    venv.join(if cfg!(windows) { "Scripts" } else { "bin" })
+}
+
+impl PipxHome {
+    /// Finds the app `name` in one of `pipx`'s venvs,
+    /// using each venv's `pipx_metadata.json` to see which apps it exposes.
+    ///
+    /// The venv named `name` is checked first,
+    /// since most apps are installed in a venv of the same name.
+    pub fn find_app(&self, name: &OsStr) -> Option<PipxApp> {
+        let venvs = self.venvs();
+        let same_name = venvs.join(name);
+        let others = fs::read_dir(venvs.as_path())
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|venv| venv != &same_name);
+        Some(same_name.clone())
+            .into_iter()
+            .chain(others)
+            .find(|venv| PipxMetadata::read(venv)
+                .map(|metadata| metadata.has_app(name))
+                .unwrap_or_default()
+            )
+            .map(|venv| PipxApp {
+                path: venv_bin(venv.as_path()).join(name),
+                venv,
+            })
+    }
+}
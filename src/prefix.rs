@@ -0,0 +1,148 @@
+use std::{fs, io};
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::info::{is_version, parse_versioned_name};
+use crate::PyvenvCfg;
+
+/// The `sys.prefix` and `sys.base_prefix` of a `python` executable,
+/// found from its install layout the way `python` finds them itself.
+///
+/// If there's a `pyvenv.cfg` next to `python` or in its parent directory, it's a venv,
+/// so that directory is the prefix, and the base prefix is found from the `home` in `pyvenv.cfg`.
+/// Otherwise, after resolving any symlinks to `python`, the prefix is the closest parent directory
+/// with a `lib/pythonX.Y/os.py` (or `lib64/` or `pypyX.Y/`).
+#[derive(Debug)]
+pub struct PythonPrefix {
+    prefix: PathBuf,
+    base_prefix: PathBuf,
+    config: Option<PyvenvCfg>,
+}
+
+/// Why a [`PythonPrefix`] couldn't be found.
+#[derive(Debug, Error)]
+pub enum PythonPrefixError {
+    #[error("couldn't resolve python {python}")]
+    IOError { python: PathBuf, source: io::Error },
+    #[error("couldn't find the prefix of python {python}: no pyvenv.cfg next to it and no lib/pythonX.Y/os.py in any of its parent directories")]
+    NotFound { python: PathBuf },
+    #[error("couldn't find the base prefix of venv {prefix}: no lib/pythonX.Y/os.py in any parent directory of its pyvenv.cfg home")]
+    NoBasePrefix { prefix: PathBuf },
+    #[error("couldn't find the base prefix of venv {prefix}: its pyvenv.cfg has no home")]
+    NoHome { prefix: PathBuf },
+}
+
+impl PythonPrefix {
+    /// Finds the prefixes of the `python` executable at `python_path`.
+    ///
+    /// See [`PythonPrefixError`] for possible errors.
+    pub fn find(python_path: &Path) -> Result<Self, PythonPrefixError> {
+        use PythonPrefixError::*;
+        let bin_dir = python_path.parent().unwrap_or_else(|| Path::new(""));
+        let venv = [Some(bin_dir), bin_dir.parent()]
+            .iter()
+            .flatten()
+            .find_map(|dir| {
+                let contents = fs::read_to_string(dir.join("pyvenv.cfg")).ok()?;
+                Some((dir.to_path_buf(), PyvenvCfg::parse(contents.as_str())))
+            });
+        if let Some((prefix, config)) = venv {
+            let home = match config.get("home") {
+                Some(home) => PathBuf::from(home),
+                None => return Err(NoHome { prefix }),
+            };
+            let home = fs::canonicalize(home.as_path()).unwrap_or(home);
+            let base_prefix = match find_landmark(home.as_path(), config.minor_version()) {
+                Some(base_prefix) => base_prefix.to_path_buf(),
+                None => return Err(NoBasePrefix { prefix }),
+            };
+            return Ok(Self {
+                prefix,
+                base_prefix,
+                config: Some(config),
+            });
+        }
+        let real_path = fs::canonicalize(python_path).map_err(|source| IOError {
+            python: python_path.to_path_buf(),
+            source,
+        })?;
+        let version = real_path
+            .file_name()
+            .and_then(|name| parse_versioned_name(name.to_str()?))
+            .map(|(version, _)| version);
+        let prefix = real_path
+            .parent()
+            .and_then(|dir| find_landmark(dir, version.as_deref()))
+            .ok_or_else(|| NotFound {
+                python: python_path.to_path_buf(),
+            })?
+            .to_path_buf();
+        Ok(Self {
+            prefix: prefix.clone(),
+            base_prefix: prefix,
+            config: None,
+        })
+    }
+    
+    /// Like `sys.prefix`.
+    pub fn prefix(&self) -> &Path {
+        self.prefix.as_path()
+    }
+    
+    /// Like `sys.base_prefix`, which is different from the [`PythonPrefix::prefix`] in a venv.
+    pub fn base_prefix(&self) -> &Path {
+        self.base_prefix.as_path()
+    }
+    
+    pub fn is_venv(&self) -> bool {
+        self.config.is_some()
+    }
+    
+    /// The venv's `pyvenv.cfg`, if it's a venv.
+    pub fn config(&self) -> Option<&PyvenvCfg> {
+        self.config.as_ref()
+    }
+}
+
+/// The closest of `dir` and its parents that has a `lib/pythonX.Y/os.py` landmark
+/// (or `lib64/` or `pypyX.Y/`), like `python` looks for,
+/// with the `X.Y` `version` of the executable if it's known.
+/// Just a `lib/pythonX.Y/` isn't enough, since e.g. `/usr/local/lib/python3.11/dist-packages`
+/// can exist without `/usr/local` being a prefix.
+fn find_landmark<'a>(dir: &'a Path, version: Option<&str>) -> Option<&'a Path> {
+    dir.ancestors().find(|prefix| {
+        ["lib", "lib64"]
+            .iter()
+            .flat_map(|lib| fs::read_dir(prefix.join(lib)).into_iter().flatten().flatten())
+            .any(|entry| {
+                let name = entry.file_name();
+                parse_versioned_name(name.to_string_lossy().as_ref())
+                    .filter(|(dir_version, _)| is_version(dir_version, version))
+                    .is_some()
+                    && entry.path().join("os.py").is_file()
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::temp_prefix::TempPrefix;
+    
+    use super::*;
+    
+    #[test]
+    fn needs_os_py_landmark_of_matching_version() {
+        let dir = TempPrefix::new("prefix", &[
+            ("lib/python3.11/os.py", ""),
+            ("local/lib/python3.11/dist-packages/foo.py", ""),
+            ("local/lib/python3.12/os.py", ""),
+            ("local/bin/python", ""),
+        ]);
+        let bin = dir.path().join("local/bin");
+        assert_eq!(find_landmark(bin.as_path(), Some("3.11")), Some(dir.path()));
+        assert_eq!(find_landmark(bin.as_path(), None), Some(dir.path().join("local").as_path()));
+        let other = find_landmark(bin.as_path(), Some("3.13"));
+        assert_eq!(other.filter(|found| found.starts_with(dir.path())), None);
+    }
+}
@@ -0,0 +1,254 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use thiserror::Error;
+
+use crate::{PyenvRoot, PyenvVersion, PyenvVersionFrom, Virtualenv};
+
+/// A variable in a [`PromptTemplate`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum PromptVar {
+    /// The version name, like `3.9.6`.
+    Version,
+    /// `shell` or `local`.
+    Origin,
+    /// The `.python-version` file, or nothing if it's from the shell.
+    File,
+    /// The virtualenv's prompt, or nothing if it's not a virtualenv.
+    Venv,
+}
+
+impl PromptVar {
+    fn parse(name: &str) -> Option<Self> {
+        use PromptVar::*;
+        match name {
+            "version" => Some(Version),
+            "origin" => Some(Origin),
+            "file" => Some(File),
+            "venv" => Some(Venv),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PromptPart {
+    Literal(String),
+    Var(PromptVar),
+    /// Only rendered if the variable isn't empty.
+    Conditional(PromptVar, Vec<PromptPart>),
+}
+
+/// A template for a shell prompt segment, like `{version} ({origin}){venv: [{venv}]}`.
+///
+/// `{name}` is replaced by the variable `name`, one of `version`, `origin`, `file`, or `venv`,
+/// and `{name:...}` renders `...` (itself a template) only if `name` isn't empty.
+/// `{{` and `}}` are a literal `{` and `}`.
+#[derive(Debug)]
+pub struct PromptTemplate {
+    parts: Vec<PromptPart>,
+}
+
+/// Why a [`PromptTemplate`] is invalid.
+#[derive(Debug, Error)]
+pub enum PromptTemplateError {
+    #[error("unknown prompt variable {{{name}}}, expected one of version, origin, file, or venv")]
+    UnknownVar { name: String },
+    #[error("unterminated {{ in prompt template")]
+    Unterminated,
+    #[error("unmatched }} in prompt template (use }}}} for a literal }})")]
+    Unmatched,
+}
+
+impl PromptTemplate {
+    /// See [`PromptTemplateError`] for possible errors.
+    pub fn parse(template: &str) -> Result<Self, PromptTemplateError> {
+        let mut chars = template.chars().peekable();
+        let parts = parse_parts(&mut chars, false)?;
+        Ok(Self { parts })
+    }
+    
+    /// Renders the template for a `version`,
+    /// or returns [`None`] if it's not a project version, i.e. not from the shell or a local file.
+    pub fn render(&self, root: &PyenvRoot, version: &PyenvVersion) -> Option<String> {
+        if let PyenvVersionFrom::Global = version.origin() {
+            return None;
+        }
+        // only read `pyvenv.cfg` if it's needed, so the prompt is as fast as possible
+        let venv = if uses_var(&self.parts, PromptVar::Venv) {
+            Virtualenv::detect(root.version_dir(version).as_path())
+                .map(|venv| venv.prompt())
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        let value = |var: PromptVar| match var {
+            PromptVar::Version => version.name().to_string(),
+            PromptVar::Origin => version.origin().to_string(),
+            PromptVar::File => version
+                .file()
+                .map(|file| file.display().to_string())
+                .unwrap_or_default(),
+            PromptVar::Venv => venv.clone(),
+        };
+        let mut rendered = String::new();
+        render_parts(&self.parts, &value, &mut rendered);
+        Some(rendered)
+    }
+}
+
+/// Parse parts until the end, or until the closing `}` if `nested`.
+fn parse_parts(chars: &mut Peekable<Chars>, nested: bool) -> Result<Vec<PromptPart>, PromptTemplateError> {
+    use PromptTemplateError::*;
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    loop {
+        match chars.next() {
+            None if nested => return Err(Unterminated),
+            None => break,
+            Some('{') if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            Some('}') if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            Some('}') if nested => break,
+            Some('}') => return Err(Unmatched),
+            Some('{') => {
+                if !literal.is_empty() {
+                    parts.push(PromptPart::Literal(std::mem::take(&mut literal)));
+                }
+                let mut name = String::new();
+                let has_body = loop {
+                    match chars.next().ok_or(Unterminated)? {
+                        '}' => break false,
+                        ':' => break true,
+                        c => name.push(c),
+                    }
+                };
+                let var = PromptVar::parse(name.as_str()).ok_or(UnknownVar { name })?;
+                parts.push(if has_body {
+                    PromptPart::Conditional(var, parse_parts(chars, true)?)
+                } else {
+                    PromptPart::Var(var)
+                });
+            }
+            Some(c) => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(PromptPart::Literal(literal));
+    }
+    Ok(parts)
+}
+
+/// If `var` is in any of the `parts`, including in conditionals.
+fn uses_var(parts: &[PromptPart], var: PromptVar) -> bool {
+    parts.iter().any(|part| match part {
+        PromptPart::Literal(_) => false,
+        PromptPart::Var(part_var) => *part_var == var,
+        PromptPart::Conditional(part_var, body) => *part_var == var || uses_var(body, var),
+    })
+}
+
+fn render_parts(parts: &[PromptPart], value: &dyn Fn(PromptVar) -> String, rendered: &mut String) {
+    for part in parts {
+        match part {
+            PromptPart::Literal(literal) => rendered.push_str(literal),
+            PromptPart::Var(var) => rendered.push_str(value(*var).as_str()),
+            PromptPart::Conditional(var, body) => if !value(*var).is_empty() {
+                render_parts(body, value, rendered);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    
+    use crate::temp_prefix::TempPrefix;
+    
+    use super::*;
+    
+    fn root(path: PathBuf) -> PyenvRoot {
+        PyenvRoot { root: path }
+    }
+    
+    fn version(name: &str, from: PyenvVersionFrom, file: Option<&str>) -> PyenvVersion {
+        PyenvVersion {
+            version: name.to_string(),
+            from,
+            file: file.map(PathBuf::from),
+        }
+    }
+    
+    fn render(template: &str, version: &PyenvVersion) -> Option<String> {
+        let root = root(PathBuf::from("/nonexistent/pyenv"));
+        PromptTemplate::parse(template).unwrap().render(&root, version)
+    }
+    
+    #[test]
+    fn renders_vars() {
+        let local = version("3.9.6", PyenvVersionFrom::Local, Some("/proj/.python-version"));
+        assert_eq!(
+            render("{version} ({origin}) {file}", &local).as_deref(),
+            Some("3.9.6 (local) /proj/.python-version"),
+        );
+        let shell = version("3.10.0", PyenvVersionFrom::Shell, None);
+        assert_eq!(render("{version} ({origin}) {file}", &shell).as_deref(), Some("3.10.0 (shell) "));
+    }
+    
+    #[test]
+    fn renders_nothing_for_global_versions() {
+        let global = version("3.9.6", PyenvVersionFrom::Global, Some("/pyenv/version"));
+        assert_eq!(render("{version}", &global), None);
+    }
+    
+    #[test]
+    fn renders_conditionals_only_if_set() {
+        let local = version("3.9.6", PyenvVersionFrom::Local, Some("/proj/.python-version"));
+        let shell = version("3.9.6", PyenvVersionFrom::Shell, None);
+        let template = "{version}{file: from {file}}{venv: [{venv}]}";
+        assert_eq!(render(template, &local).as_deref(), Some("3.9.6 from /proj/.python-version"));
+        assert_eq!(render(template, &shell).as_deref(), Some("3.9.6"));
+    }
+    
+    #[test]
+    fn renders_venv_prompt() {
+        let dir = TempPrefix::new("prompt", &[
+            ("versions/proj-venv/pyvenv.cfg", "home = /usr/bin\nprompt = 'proj'\n"),
+        ]);
+        let root = root(dir.path().to_path_buf());
+        let local = version("proj-venv", PyenvVersionFrom::Local, Some("/proj/.python-version"));
+        let rendered = PromptTemplate::parse("{version}{venv: [{venv}]}").unwrap().render(&root, &local);
+        assert_eq!(rendered.as_deref(), Some("proj-venv [proj]"));
+    }
+    
+    #[test]
+    fn finds_used_vars() {
+        let parts = |template| PromptTemplate::parse(template).unwrap().parts;
+        assert!(uses_var(&parts("{version} [{venv}]"), PromptVar::Venv));
+        assert!(uses_var(&parts("{venv: [venv]}"), PromptVar::Venv));
+        assert!(uses_var(&parts("{file: [{venv}]}"), PromptVar::Venv));
+        assert!(!uses_var(&parts("{version} ({origin}) {{venv}}"), PromptVar::Venv));
+    }
+    
+    #[test]
+    fn renders_escaped_braces() {
+        let shell = version("3.9.6", PyenvVersionFrom::Shell, None);
+        assert_eq!(render("{{{version}}}", &shell).as_deref(), Some("{3.9.6}"));
+        assert_eq!(render("{version:{{}}}", &shell).as_deref(), Some("{}"));
+    }
+    
+    #[test]
+    fn rejects_invalid_templates() {
+        let error = |template| PromptTemplate::parse(template).unwrap_err().to_string();
+        assert_eq!(error("{ver}"), "unknown prompt variable {ver}, expected one of version, origin, file, or venv");
+        assert_eq!(error("{version"), "unterminated { in prompt template");
+        assert_eq!(error("{version: [{venv}]"), "unterminated { in prompt template");
+        assert_eq!(error("version}"), "unmatched } in prompt template (use }} for a literal })");
+    }
+}
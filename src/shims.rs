@@ -0,0 +1,58 @@
+use std::{env, fs};
+use std::path::{Path, PathBuf};
+
+use crate::PyenvRoot;
+
+/// The shim directories of version managers (`pyenv`, `asdf`, and `mise`),
+/// whose `python` shims would just run us again.
+#[derive(Debug)]
+pub struct ShimDirs {
+    dirs: Vec<PathBuf>,
+}
+
+impl ShimDirs {
+    /// The known shim directories, including `$PYENV_ROOT/shims` of `pyenv_root`.
+    pub fn new(pyenv_root: Option<&PyenvRoot>) -> Self {
+        let home = dirs_next::home_dir();
+        let data_dir = |var: &str, default: &[&str]| env::var_os(var)
+            .map(PathBuf::from)
+            .or_else(|| Some(default.iter().fold(home.clone()?, |dir, name| dir.join(name))));
+        let dirs = [
+            pyenv_root.map(|root| root.path().to_path_buf()),
+            data_dir("ASDF_DATA_DIR", &[".asdf"]),
+            // `mise` uses `~/.local/share` on all platforms
+            data_dir("MISE_DATA_DIR", &[".local", "share", "mise"]),
+        ]
+            .iter()
+            .flatten()
+            .map(|dir| dir.join("shims"))
+            .collect();
+        Self { dirs }
+    }
+    
+    /// If `dir` is one of the known shim directories,
+    /// or is the `shims` directory of any other `pyenv` root.
+    pub fn contains(&self, dir: &Path) -> bool {
+        self.dirs.iter().any(|shims| shims == dir) || is_pyenv_shims(dir)
+    }
+}
+
+/// If `dir` is the `shims` directory of a `pyenv` root,
+/// i.e. it's named `shims` and is next to a `versions` directory.
+fn is_pyenv_shims(dir: &Path) -> bool {
+    dir.file_name().map(|name| name == "shims").unwrap_or_default()
+        && dir.parent().map(|root| root.join("versions").is_dir()).unwrap_or_default()
+}
+
+/// If the files at `a` and `b` have the same contents,
+/// e.g. when one is a copy (not a link) of the other.
+pub fn same_contents(a: &Path, b: &Path) -> bool {
+    let same_len = match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.len() == b.len(),
+        _ => false,
+    };
+    same_len && match (fs::read(a), fs::read(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
@@ -0,0 +1,43 @@
+//! A test fixture shared by the library's and the `python` binary's tests,
+//! which includes it with `#[path]` since it's not part of the library's API.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A temporary directory (usually a `python` prefix) with the given files, removed on drop.
+pub struct TempPrefix {
+    path: PathBuf,
+}
+
+impl TempPrefix {
+    /// Creates the directory, unique to this process and `name`, with the `files` as `(path, contents)`.
+    /// Contents like `-> target` make a symlink to `target` instead (only on Unix).
+    pub fn new(name: &str, files: &[(&str, &str)]) -> Self {
+        let path = std::env::temp_dir().join(format!("pyenv-python-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(path.as_path());
+        for (file, contents) in files {
+            let file = path.join(file);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            match contents.strip_prefix("-> ") {
+                #[cfg(unix)]
+                Some(target) => std::os::unix::fs::symlink(target, file).unwrap(),
+                _ => fs::write(file, contents).unwrap(),
+            }
+        }
+        fs::create_dir_all(path.as_path()).unwrap();
+        Self { path }
+    }
+    
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+}
+
+impl Drop for TempPrefix {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(self.path.as_path());
+    }
+}
+
+pub const PATCHLEVEL_3_11: &str = "#define PY_VERSION \"3.11.4\"\n";
+pub const PATCHLEVEL_3_12: &str = "#define PY_VERSION \"3.12.0\"\n";
@@ -0,0 +1,75 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::config;
+
+/// Where [`trace!`](crate::trace!) messages explaining each lookup step are written.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TraceDestination {
+    Off,
+    /// For `--explain`, where the trace is the output.
+    Stdout,
+    /// For `$PYENV_PYTHON_DEBUG`, so the trace doesn't mix with the program's output.
+    Stderr,
+}
+
+/// The [`TraceDestination`] as a `u8`, or [`UNSET`] if it hasn't been set or read from the environment yet.
+static DESTINATION: AtomicU8 = AtomicU8::new(UNSET);
+
+const UNSET: u8 = u8::MAX;
+
+impl TraceDestination {
+    fn from_u8(destination: u8) -> Self {
+        match destination {
+            1 => Self::Stdout,
+            2 => Self::Stderr,
+            _ => Self::Off,
+        }
+    }
+}
+
+/// Sets where traces are written, overriding `$PYENV_PYTHON_DEBUG`.
+pub fn set_destination(destination: TraceDestination) {
+    DESTINATION.store(destination as u8, Ordering::Relaxed);
+}
+
+/// Where traces are written, which is [`TraceDestination::Stderr`] if `$PYENV_PYTHON_DEBUG` is set,
+/// unless it's been set by [`set_destination`].
+pub fn destination() -> TraceDestination {
+    match DESTINATION.load(Ordering::Relaxed) {
+        UNSET => {
+            let destination = if config::flag("DEBUG", false) {
+                TraceDestination::Stderr
+            } else {
+                TraceDestination::Off
+            };
+            set_destination(destination);
+            destination
+        }
+        destination => TraceDestination::from_u8(destination),
+    }
+}
+
+pub fn is_enabled() -> bool {
+    destination() != TraceDestination::Off
+}
+
+/// Writes a trace message, which should go through [`trace!`](crate::trace!) instead.
+pub fn write(args: fmt::Arguments) {
+    match destination() {
+        TraceDestination::Off => {}
+        TraceDestination::Stdout => println!("{}", args),
+        TraceDestination::Stderr => eprintln!("pyenv-python: debug: {}", args),
+    }
+}
+
+/// Traces a step in looking up `python`, formatted like [`format!`],
+/// if tracing is enabled (see [`destination`]).
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::trace::is_enabled() {
+            $crate::trace::write(format_args!($($arg)*));
+        }
+    };
+}
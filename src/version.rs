@@ -19,8 +19,8 @@ impl<T, E> FlipResult<T, E> for Result<T, E> {
 }
 
 impl PyenvVersion {
-    pub fn from(from: PyenvVersionFrom) -> impl Fn(String) -> Self {
-        move |version| Self { version, from }
+    pub fn from(from: PyenvVersionFrom) -> impl Fn((String, Option<PathBuf>)) -> Self {
+        move |(version, file)| Self { version, from, file }
     }
 }
 
@@ -31,36 +31,55 @@ fn read_python_version_file(path: &Path) -> io::Result<String> {
     Ok(version)
 }
 
-fn from_local_python_version_file_given_cwd(cwd: &Path) -> Result<io::Error, String> {
+fn trace_python_version_file(path: &Path, version: &io::Result<String>) {
+    match version {
+        Ok(version) => crate::trace!("found version {} in {}", version, path.display()),
+        Err(e) if e.kind() == ErrorKind::NotFound => crate::trace!("no version in {}", path.display()),
+        Err(e) => crate::trace!("couldn't read version from {}: {}", path.display(), e),
+    }
+}
+
+fn from_local_python_version_file_given_cwd(cwd: &Path) -> Result<io::Error, (String, PathBuf)> {
     for dir in cwd.ancestors() {
         let path = dir.join(".python-version");
-        read_python_version_file(path.as_path()).flip()?;
+        let version = read_python_version_file(path.as_path());
+        trace_python_version_file(path.as_path(), &version);
+        version
+            .map(|version| (version, path))
+            .flip()?;
     }
     Ok(ErrorKind::NotFound.into())
 }
 
-fn from_local_python_version_file() -> io::Result<String> {
+fn from_local_python_version_file() -> io::Result<(String, Option<PathBuf>)> {
     let cwd = env::current_dir()?;
-    let version = from_local_python_version_file_given_cwd(cwd.as_path()).flip()?;
-    Ok(version)
+    let (version, path) = from_local_python_version_file_given_cwd(cwd.as_path()).flip()?;
+    Ok((version, Some(path)))
 }
 
 fn global_python_version_file_path(root: &Path) -> PathBuf {
     root.join("version")
 }
 
-fn from_global_python_version_file(root: &Path) -> io::Result<String> {
+fn from_global_python_version_file(root: &Path) -> io::Result<(String, Option<PathBuf>)> {
     let path = global_python_version_file_path(root);
-    read_python_version_file(path.as_path())
+    let version = read_python_version_file(path.as_path());
+    trace_python_version_file(path.as_path(), &version);
+    Ok((version?, Some(path)))
 }
 
 // use inverted Result<>s here to short circuit on success instead of failure
 fn as_result(root: &Path) -> Result<(), PyenvVersion> {
     use PyenvVersionFrom::*;
-    fn f<E>(version: PyenvVersionFrom, result: Result<String, E>) -> Result<E, PyenvVersion> {
+    fn f<E>(version: PyenvVersionFrom, result: Result<(String, Option<PathBuf>), E>) -> Result<E, PyenvVersion> {
         result.map(PyenvVersion::from(version)).flip()
     }
-    f(Shell, env::var("PYENV_VERSION"))?;
+    let shell_version = env::var("PYENV_VERSION");
+    match &shell_version {
+        Ok(version) => crate::trace!("$PYENV_VERSION is {}", version),
+        Err(_) => crate::trace!("$PYENV_VERSION is unset"),
+    }
+    f(Shell, shell_version.map(|version| (version, None)))?;
     f(Local, from_local_python_version_file())?;
     f(Global, from_global_python_version_file(root))?;
     Ok(())
@@ -69,3 +88,16 @@ fn as_result(root: &Path) -> Result<(), PyenvVersion> {
 pub fn pyenv_version(root: &Path) -> Option<PyenvVersion> {
     as_result(root).err()
 }
+
+/// The version file that's used if `$PYENV_VERSION` isn't set, like `pyenv version-file`,
+/// i.e. the nearest local `.python-version`, or else the global `version` file (even if it doesn't exist).
+pub fn pyenv_version_file(root: &Path) -> PathBuf {
+    env::current_dir()
+        .ok()
+        .and_then(|cwd| cwd
+            .ancestors()
+            .map(|dir| dir.join(".python-version"))
+            .find(|path| path.is_file())
+        )
+        .unwrap_or_else(|| global_python_version_file_path(root))
+}
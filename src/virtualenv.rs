@@ -0,0 +1,120 @@
+use std::{fmt, fs};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// The `key = value` entries of a `pyvenv.cfg` file.
+#[derive(Debug, Default)]
+pub struct PyvenvCfg {
+    entries: Vec<(String, String)>,
+}
+
+impl PyvenvCfg {
+    /// Parses a `pyvenv.cfg` file the same way `site.py` does,
+    /// ignoring any lines that aren't `key = value`.
+    pub fn parse(contents: &str) -> Self {
+        let entries = contents
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_at(line.find('=')?);
+                Some((key.trim().to_lowercase(), value[1..].trim().to_string()))
+            })
+            .collect();
+        Self { entries }
+    }
+    
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+    
+    /// The `X.Y.Z` version of the venv's `python`,
+    /// from the `version` that `venv` writes or the `version_info` that `virtualenv` writes,
+    /// which is like `3.9.6.final.0`.
+    pub fn version(&self) -> Option<&str> {
+        let version = self.get("version").or_else(|| self.get("version_info"))?;
+        Some(version_prefix(version, 3))
+    }
+    
+    /// The `X.Y` of [`PyvenvCfg::version`].
+    pub fn minor_version(&self) -> Option<&str> {
+        Some(version_prefix(self.version()?, 2))
+    }
+}
+
+/// The first `parts` `.`-separated parts of `version`.
+fn version_prefix(version: &str, parts: usize) -> &str {
+    match version.match_indices('.').nth(parts - 1) {
+        Some((i, _)) => &version[..i],
+        None => version,
+    }
+}
+
+/// A virtualenv, either created by `venv` or `virtualenv`
+/// (e.g. through `pyenv-virtualenv`).
+#[derive(Debug)]
+pub struct Virtualenv {
+    path: PathBuf,
+    config: PyvenvCfg,
+}
+
+impl Display for Virtualenv {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "virtualenv at {}", self.path.display())
+    }
+}
+
+impl Virtualenv {
+    /// Detects if `prefix` is a virtualenv,
+    /// i.e. it has a `pyvenv.cfg` or is a legacy `virtualenv` (before `pyvenv.cfg` was used).
+    pub fn detect(prefix: &Path) -> Option<Self> {
+        let config = match fs::read_to_string(prefix.join("pyvenv.cfg")) {
+            Ok(contents) => PyvenvCfg::parse(contents.as_str()),
+            Err(_) if prefix.join("bin").join("activate_this.py").is_file() => PyvenvCfg::default(),
+            Err(_) => return None,
+        };
+        Some(Self {
+            path: prefix.to_path_buf(),
+            config,
+        })
+    }
+    
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    
+    pub fn config(&self) -> &PyvenvCfg {
+        &self.config
+    }
+    
+    /// The prompt `activate` would use, i.e. the `prompt` in `pyvenv.cfg` or else the directory name.
+    pub fn prompt(&self) -> String {
+        self.config
+            .get("prompt")
+            .map(|prompt| prompt.trim_matches(|c| c == '\'' || c == '"').to_string())
+            .or_else(|| self.path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+            )
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn reads_versions() {
+        let venv = PyvenvCfg::parse("home = /usr/bin\nversion = 3.9.6\n");
+        assert_eq!(venv.version(), Some("3.9.6"));
+        assert_eq!(venv.minor_version(), Some("3.9"));
+        let virtualenv = PyvenvCfg::parse("home = /usr/bin\nversion_info = 3.10.0.final.0\n");
+        assert_eq!(virtualenv.version(), Some("3.10.0"));
+        assert_eq!(virtualenv.minor_version(), Some("3.10"));
+        let none = PyvenvCfg::parse("home = /usr/bin\n");
+        assert_eq!(none.version(), None);
+        assert_eq!(none.minor_version(), None);
+    }
+}